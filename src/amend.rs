@@ -1,12 +1,45 @@
-use crate::game::{self, Game, GameState, Piece, PieceVariant, TeamColor};
+use crate::bitboard::Position;
+use crate::computer::{Computer, Difficulty};
+use crate::fen;
+use crate::game::{
+    self, Analysis, Board, Game, GameData, GameOverReason, GameResult, GameState, TeamColor,
+};
+use crate::pgn;
 use crate::produce::{self, Move, MoveType};
+use crate::san;
 use crate::Command;
 
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
 fn select_team(game: &mut Game, color: TeamColor) {
     game.state = GameState::BoardGame;
+    game.undo_stack.clear();
+    game.redo_stack.clear();
     game.cache.data = game::initialize_data();
     game.cache.data.player_color = color;
-    game::init_chess_position(game, color)
+    if let Some(ref mut computer) = game.computer {
+        computer.color = color.opposite();
+        computer.reset_clock();
+    }
+
+    let is_loaded = game
+        .starting_position
+        .clone()
+        .is_some_and(|position| fen::load(game, &position).is_ok());
+    if !is_loaded {
+        game::init_chess_position(game)
+    }
+}
+
+/// Picks the strength of the computer and starts the game set up by
+/// `select_team`.
+fn select_difficulty(game: &mut Game, difficulty: Difficulty) {
+    if let Some(ref mut computer) = game.computer {
+        computer.difficulty = difficulty;
+        computer.reset_clock();
+    }
+    game.state = GameState::BoardGame;
 }
 
 fn focus_square(game: &mut Game, column: usize, row: usize) {
@@ -26,70 +59,257 @@ fn unfocus_square(game: &mut Game) {
     game.cache.data.danger_squares.clear();
 }
 
+/// Gets the side to move ready to play: generates its legal moves and
+/// records the position for the repetition rules.
+pub fn begin_turn(board: &Board, data: &mut GameData) {
+    data.available_moves = produce::generate_legal_moves(board, data);
+
+    data.position_history.push(data.hash);
+}
+
 fn change_turn(game: &mut Game) {
-    game.cache.data.current_turn = if game.cache.data.current_turn == TeamColor::White {
-        TeamColor::Black
+    begin_turn(&game.board, &mut game.cache.data);
+    end_game_if_over(game);
+}
+
+/// How the game ended if the side to move is mated or stalemated, or if the
+/// position is drawn without either player having to claim it.
+pub fn game_over(board: &Board, data: &GameData) -> Option<(GameResult, GameOverReason)> {
+    let color = data.current_turn;
+    let reason = if data.available_moves.is_empty() {
+        if produce::is_in_check(board, data, color) {
+            return Some((
+                GameResult::win_for(color.opposite()),
+                GameOverReason::Checkmate,
+            ));
+        }
+        GameOverReason::Stalemate
+    } else if data.repetition_count() >= 5 {
+        GameOverReason::FivefoldRepetition
+    } else if data.halfmove_clock >= 150 {
+        GameOverReason::SeventyFiveMoveRule
+    } else if produce::is_insufficient_material(board) {
+        GameOverReason::InsufficientMaterial
     } else {
-        TeamColor::White
+        return None;
     };
-    game.cache.data.available_moves = produce::generate_moves(game);
+
+    Some((GameResult::Draw, reason))
+}
+
+fn end_game_if_over(game: &mut Game) {
+    if let Some((result, reason)) = game_over(&game.board, &game.cache.data) {
+        game.state = GameState::GameOver(result, reason);
+    }
 }
 
-fn update_castling_data(game: &mut Game, ptr: usize, column: usize) {
-    let column_ptr = if column == 0 { 0 } else { 1 };
-    if !game.cache.data.is_valid_castling[ptr][column_ptr] {
-        return;
+fn claim_draw(game: &mut Game) {
+    if let Some(reason) = game.cache.data.claimable_draw() {
+        game.state = GameState::GameOver(GameResult::Draw, reason);
     }
-    game.cache.data.is_valid_castling[ptr][column_ptr] = false;
+}
+
+fn resign(game: &mut Game) {
+    // against the computer it is always the player who resigns
+    let loser = match game.computer {
+        Some(ref computer) => computer.color.opposite(),
+        None => game.cache.data.current_turn,
+    };
+    let winner = loser.opposite();
+    game.state = GameState::GameOver(GameResult::win_for(winner), GameOverReason::Resignation);
+}
+
+/// Writes `move_data` to the move list and makes it on the board.
+pub fn apply_move(board: &mut Board, data: &mut GameData, move_data: &Move) {
+    let notation = san::render(board, data, move_data);
+    data.move_list.push(notation);
+    produce::make_move(board, data, move_data);
 }
 
 fn move_piece(game: &mut Game, move_data: Move) {
-    let piece_taken = game.board[move_data.from.1][move_data.from.0].take();
-    game.cache.data.recent_advancing_pawn = None;
+    game.undo_stack.push((game.board, game.cache.data.clone()));
+    game.redo_stack.clear();
+
     game.cache.data.recent_promoting_pawn = None;
+    if let MoveType::Promotion(_) = move_data.variant {
+        game.state = GameState::BoardGame;
+    }
 
-    if let Some(piece) = piece_taken {
-        use PieceVariant::*;
-        let ptr = if piece.color == game.cache.data.player_color {
-            0
-        } else {
-            1
-        };
-        match piece.variant {
-            King => game.cache.data.is_valid_castling[ptr] = [false; 2],
-            Castle if [0, 7].contains(&move_data.from.0) => {
-                update_castling_data(game, ptr, move_data.from.0)
-            }
-            _ => (),
-        }
+    apply_move(&mut game.board, &mut game.cache.data, &move_data);
+}
+
+fn export_pgn(game: &Game) {
+    match pgn::save(game) {
+        Ok(path) => println!("saved game to {}", path),
+        Err(error) => eprintln!("could not save game: {}", error),
     }
+}
 
-    game.board[move_data.to.1][move_data.to.0] = match move_data.variant {
-        MoveType::Promotion(variant) => {
-            game.state = GameState::BoardGame;
-            Some(Piece {
-                variant,
-                color: game.cache.data.current_turn,
-            })
-        }
-        MoveType::AdvancePawn => {
-            game.cache.data.recent_advancing_pawn = Some(move_data.to);
-            piece_taken
+fn export_fen(game: &Game) {
+    match fen::save(game) {
+        Ok(path) => println!("saved position to {}", path),
+        Err(error) => eprintln!("could not save position: {}", error),
+    }
+}
+
+/// Puts a position taken from the undo or redo stack back on the board,
+/// dropping whatever was selected when it was saved.
+fn restore_position(game: &mut Game, (board, data): (Board, GameData)) {
+    game.state = GameState::BoardGame;
+    game.board = board;
+    game.cache.data = data;
+    game.cache.data.focused_square = None;
+    game.cache.data.danger_squares.clear();
+    game.cache.data.recent_promoting_pawn = None;
+    game.cache.data.typed_move.clear();
+    end_game_if_over(game);
+}
+
+/// Moves positions from the `from` stack over to the `to` stack, starting
+/// with `current`, until one comes up where the side to move is not the
+/// `computer` or the game is over. Returns that position, or `None` when
+/// `from` is empty.
+fn step_through(
+    from: &mut Vec<(Board, GameData)>,
+    to: &mut Vec<(Board, GameData)>,
+    mut current: (Board, GameData),
+    computer: Option<TeamColor>,
+) -> Option<(Board, GameData)> {
+    let mut is_moved = false;
+    while let Some(position) = from.pop() {
+        to.push(current);
+        current = position;
+        is_moved = true;
+
+        let (board, data) = &current;
+        if computer != Some(data.current_turn) || game_over(board, data).is_some() {
+            break;
         }
-        MoveType::EnPassant => {
-            game.board[move_data.from.1][move_data.to.0].take();
-            piece_taken
+    }
+    is_moved.then_some(current)
+}
+
+/// Against the computer, undo and redo skip over its moves so the player
+/// lands on their own turn again.
+fn undo(game: &mut Game) {
+    let computer = game.computer.as_ref().map(|computer| computer.color);
+    let current = (game.board, game.cache.data.clone());
+    if let Some(position) = step_through(
+        &mut game.undo_stack,
+        &mut game.redo_stack,
+        current,
+        computer,
+    ) {
+        restore_position(game, position);
+    }
+}
+
+fn redo(game: &mut Game) {
+    let computer = game.computer.as_ref().map(|computer| computer.color);
+    let current = (game.board, game.cache.data.clone());
+    if let Some(position) = step_through(
+        &mut game.redo_stack,
+        &mut game.undo_stack,
+        current,
+        computer,
+    ) {
+        restore_position(game, position);
+    }
+}
+
+/// Puts the current position of the replay on the board.
+fn show_replay_position(game: &mut Game) {
+    let replay = match game.replay {
+        Some(ref replay) => replay,
+        None => return,
+    };
+    if let Some((board, data)) = replay.positions.get(replay.ply) {
+        let player_color = game.cache.data.player_color;
+        game.board = *board;
+        game.cache.data = data.clone();
+        game.cache.data.player_color = player_color;
+    }
+}
+
+fn show_replay(game: &mut Game) {
+    game.state = GameState::Replay;
+    game.cache.data.player_color = TeamColor::White;
+    show_replay_position(game);
+}
+
+fn step_replay(game: &mut Game, forward: bool) {
+    if let Some(ref mut replay) = game.replay {
+        if forward && replay.ply + 1 < replay.positions.len() {
+            replay.ply += 1;
+        } else if !forward && replay.ply > 0 {
+            replay.ply -= 1;
         }
-        MoveType::Castling(column) => {
-            let to_column = if column == 7 {
-                move_data.to.0 - 1
-            } else {
-                move_data.to.0 + 1
-            };
-            game.board[move_data.from.1][to_column] = game.board[move_data.from.1][column].take();
-            piece_taken
+    }
+    show_replay_position(game);
+}
+
+fn switch_replay_game(game: &mut Game, forward: bool) {
+    if let Some(ref mut replay) = game.replay {
+        let game_index = if forward {
+            replay.game_index + 1
+        } else {
+            replay.game_index.saturating_sub(1)
+        };
+        replay.select_game(game_index);
+    }
+    show_replay_position(game);
+}
+
+/// Starts probing the tablebases for the position on the board. Large
+/// tables are read from disk, so the probe runs on its own thread the way
+/// the computer's search does.
+fn analyze(game: &Game) -> Analysis {
+    let tablebases = game.tablebases.clone();
+    let position = Position::from_board(&game.board, &game.cache.data);
+    let probe = tablebases.map(|tablebases| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // the view may have moved on already, which is fine
+            let _ = sender.send(tablebases.probe(&position));
+        });
+        receiver
+    });
+    Analysis {
+        hash: game.cache.data.hash,
+        verdict: None,
+        probe,
+    }
+}
+
+fn toggle_analysis(game: &mut Game) {
+    game.cache.analysis = match game.cache.analysis {
+        Some(_) => None,
+        None => Some(analyze(game)),
+    };
+}
+
+/// Keeps the analysis view, when shown, on the position on the board and
+/// picks up the verdict once the probe has answered.
+fn refresh_analysis(game: &mut Game) {
+    let is_stale = game
+        .cache
+        .analysis
+        .as_ref()
+        .is_some_and(|analysis| analysis.hash != game.cache.data.hash);
+    if is_stale {
+        game.cache.analysis = Some(analyze(game));
+    }
+
+    if let Some(ref mut analysis) = game.cache.analysis {
+        let answer = analysis.probe.as_ref().map(Receiver::try_recv);
+        match answer {
+            Some(Ok(verdict)) => {
+                analysis.verdict = verdict;
+                analysis.probe = None;
+            }
+            Some(Err(TryRecvError::Disconnected)) => analysis.probe = None,
+            Some(Err(TryRecvError::Empty)) | None => (),
         }
-        _ => piece_taken,
     }
 }
 
@@ -102,9 +322,40 @@ pub fn update(instructions: Vec<Command>, game: &mut Game) {
     for command in instructions {
         match command {
             Command::Quit => std::process::exit(0),
-            Command::Play => game.state = GameState::TeamSelection,
+            Command::Play => {
+                game.computer = None;
+                game.state = GameState::TeamSelection;
+            }
+            Command::PlayComputer => {
+                game.computer = Some(Computer::new(game.hash_megabytes));
+                game.state = GameState::TeamSelection;
+            }
             Command::ExitGame => game.state = GameState::StartMenu,
-            Command::SelectTeam(color) => select_team(game, color),
+            Command::SelectTeam(color) => {
+                select_team(game, color);
+                if game.computer.is_some() {
+                    game.state = GameState::DifficultySelection;
+                }
+            }
+            Command::SelectDifficulty(difficulty) => select_difficulty(game, difficulty),
+            Command::Rematch => select_team(game, game.cache.data.player_color),
+            Command::Resign => resign(game),
+            Command::ClaimDraw => claim_draw(game),
+            Command::ExportFen => export_fen(game),
+            Command::ExportPgn => export_pgn(game),
+            Command::ToggleAnalysis => toggle_analysis(game),
+            Command::Undo => undo(game),
+            Command::Redo => redo(game),
+            Command::TypeMove(text) => game.cache.data.typed_move.push_str(&text),
+            Command::EraseTypedMove => {
+                game.cache.data.typed_move.pop();
+            }
+            Command::ClearTypedMove => game.cache.data.typed_move.clear(),
+            Command::ShowReplay => show_replay(game),
+            Command::StepForward => step_replay(game, true),
+            Command::StepBackward => step_replay(game, false),
+            Command::NextGame => switch_replay_game(game, true),
+            Command::PreviousGame => switch_replay_game(game, false),
             Command::Focus(c, r) => focus_square(game, c, r),
             Command::ChangeTurn => change_turn(game),
             Command::Unfocus => unfocus_square(game),
@@ -113,4 +364,141 @@ pub fn update(instructions: Vec<Command>, game: &mut Game) {
             Command::Idle => (),
         }
     }
+    refresh_analysis(game);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci;
+
+    fn play(board: &mut Board, data: &mut GameData, moves: &str) {
+        for uci_move in moves.split_whitespace() {
+            let move_data = uci::parse(data, uci_move).unwrap();
+            apply_move(board, data, &move_data);
+            begin_turn(board, data);
+        }
+    }
+
+    /// Plays `moves` the way the board does, saving the position before
+    /// each one on `undo_stack`, and returns every position passed through.
+    fn play_with_undo(
+        board: &mut Board,
+        data: &mut GameData,
+        undo_stack: &mut Vec<(Board, GameData)>,
+        moves: &str,
+    ) -> Vec<(Board, GameData)> {
+        let mut positions = vec![(*board, data.clone())];
+        for uci_move in moves.split_whitespace() {
+            undo_stack.push((*board, data.clone()));
+            play(board, data, uci_move);
+            positions.push((*board, data.clone()));
+        }
+        positions
+    }
+
+    #[test]
+    fn undo_and_redo_restore_every_position() {
+        let (mut board, mut data) =
+            fen::parse("r3k2r/8/8/8/3p4/8/3PP3/R3K2R w KQkq - 0 1").unwrap();
+        let mut undo_stack = vec![];
+        let mut redo_stack = vec![];
+        // both sides castle, then an en passant capture is taken back
+        let positions = play_with_undo(
+            &mut board,
+            &mut data,
+            &mut undo_stack,
+            "e1g1 e8c8 e2e4 d4e3 d2e3",
+        );
+        assert_eq!(data.move_list, ["O-O", "O-O-O", "e4", "dxe3", "dxe3"]);
+
+        let mut current = positions.last().unwrap().clone();
+        for expected in positions.iter().rev().skip(1) {
+            current = step_through(&mut undo_stack, &mut redo_stack, current, None).unwrap();
+            assert_eq!(&current, expected);
+        }
+        assert!(step_through(&mut undo_stack, &mut redo_stack, current.clone(), None).is_none());
+
+        for expected in positions.iter().skip(1) {
+            current = step_through(&mut redo_stack, &mut undo_stack, current, None).unwrap();
+            assert_eq!(&current, expected);
+        }
+        assert!(redo_stack.is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_skip_the_computer_moves() {
+        let (mut board, mut data) = fen::parse(fen::STARTING_POSITION).unwrap();
+        let mut undo_stack = vec![];
+        let mut redo_stack = vec![];
+        let positions = play_with_undo(
+            &mut board,
+            &mut data,
+            &mut undo_stack,
+            "e2e4 e7e5 g1f3 b8c6",
+        );
+        let computer = Some(TeamColor::Black);
+
+        let current = positions[4].clone();
+        let current = step_through(&mut undo_stack, &mut redo_stack, current, computer).unwrap();
+        assert_eq!(current, positions[2]);
+        assert_eq!(current.1.current_turn, TeamColor::White);
+
+        let current = step_through(&mut redo_stack, &mut undo_stack, current, computer).unwrap();
+        assert_eq!(current, positions[4]);
+        assert_eq!(undo_stack.len(), 4);
+    }
+
+    #[test]
+    fn threefold_is_claimed_and_fivefold_ends_the_game() {
+        let (mut board, mut data) = fen::parse(fen::STARTING_POSITION).unwrap();
+        let knights_out_and_back = "g1f3 g8f6 f3g1 f6g8";
+
+        play(&mut board, &mut data, knights_out_and_back);
+        assert_eq!(data.claimable_draw(), None);
+
+        play(&mut board, &mut data, knights_out_and_back);
+        assert_eq!(data.repetition_count(), 3);
+        assert_eq!(
+            data.claimable_draw(),
+            Some(GameOverReason::ThreefoldRepetition)
+        );
+        assert_eq!(game_over(&board, &data), None);
+
+        play(&mut board, &mut data, knights_out_and_back);
+        play(&mut board, &mut data, knights_out_and_back);
+        assert_eq!(data.repetition_count(), 5);
+        assert_eq!(
+            game_over(&board, &data),
+            Some((GameResult::Draw, GameOverReason::FivefoldRepetition))
+        );
+    }
+
+    #[test]
+    fn fifty_moves_are_claimed_and_seventy_five_end_the_game() {
+        let (board, data) = fen::parse("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        assert_eq!(data.claimable_draw(), Some(GameOverReason::FiftyMoveRule));
+        assert_eq!(game_over(&board, &data), None);
+
+        let (board, data) = fen::parse("4k3/8/8/8/8/8/8/R3K3 w - - 150 105").unwrap();
+        assert_eq!(
+            game_over(&board, &data),
+            Some((GameResult::Draw, GameOverReason::SeventyFiveMoveRule))
+        );
+    }
+
+    #[test]
+    fn mate_and_stalemate_end_the_game() {
+        let (board, data) = fen::parse("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(
+            game_over(&board, &data),
+            Some((GameResult::WhiteWins, GameOverReason::Checkmate))
+        );
+
+        let (board, data) = fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(
+            game_over(&board, &data),
+            Some((GameResult::Draw, GameOverReason::Stalemate))
+        );
+    }
 }
@@ -0,0 +1,867 @@
+use crate::fen::{self, FenError};
+use crate::game::{Board, GameData, Piece, PieceVariant, TeamColor};
+use crate::produce::{Move, MoveType};
+use crate::zobrist;
+
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+/// One bit per square, bit `row * 8 + column`, so a1 is bit 0 and h8 bit 63.
+pub type Bitboard = u64;
+
+/// Piece kinds in the order of `PieceVariant::index`.
+pub const VARIANTS: [PieceVariant; 6] = [
+    PieceVariant::King,
+    PieceVariant::Queen,
+    PieceVariant::Castle,
+    PieceVariant::Bishop,
+    PieceVariant::Knight,
+    PieceVariant::Pawn,
+];
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+pub fn square_index(square: (usize, usize)) -> usize {
+    square.1 * 8 + square.0
+}
+
+pub fn square_coordinates(index: usize) -> (usize, usize) {
+    (index % 8, index / 8)
+}
+
+fn offset_square(index: usize, delta: (i32, i32)) -> Option<usize> {
+    let column = (index % 8) as i32 + delta.0;
+    let row = (index / 8) as i32 + delta.1;
+    if (0..8).contains(&column) && (0..8).contains(&row) {
+        Some((row * 8 + column) as usize)
+    } else {
+        None
+    }
+}
+
+/// Iterates over the indices of the set bits, lowest first.
+pub fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let index = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(index)
+    })
+}
+
+/// Attacks of a sliding piece found by walking each ray, used to fill the
+/// magic tables.
+fn sliding_attacks(index: usize, occupied: Bitboard, directions: &[(i32, i32)]) -> Bitboard {
+    let mut attacks = 0;
+    for &direction in directions {
+        let mut square = index;
+        while let Some(next) = offset_square(square, direction) {
+            attacks |= 1 << next;
+            if occupied & (1 << next) != 0 {
+                break;
+            }
+            square = next;
+        }
+    }
+    attacks
+}
+
+/// The squares whose occupancy decides the attacks of a sliding piece, that
+/// is every ray square except the last one on the edge of the board.
+fn relevant_occupancy(index: usize, directions: &[(i32, i32)]) -> Bitboard {
+    let mut mask = 0;
+    for &direction in directions {
+        let mut square = index;
+        while let Some(next) = offset_square(square, direction) {
+            if offset_square(next, direction).is_none() {
+                break;
+            }
+            mask |= 1 << next;
+            square = next;
+        }
+    }
+    mask
+}
+
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct Tables {
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    pawn: [[Bitboard; 64]; 2],
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    sliding: Vec<Bitboard>,
+}
+
+fn step_attacks(deltas: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut attacks = [0; 64];
+    for (index, bitboard) in attacks.iter_mut().enumerate() {
+        for &delta in deltas {
+            if let Some(target) = offset_square(index, delta) {
+                *bitboard |= 1 << target;
+            }
+        }
+    }
+    attacks
+}
+
+/// Searches a multiplier that maps every subset of the relevant occupancy to
+/// a slot holding its attacks, sharing a slot only when the attacks agree.
+fn find_magic(
+    index: usize,
+    directions: &[(i32, i32)],
+    sliding: &mut Vec<Bitboard>,
+    seed: &mut u64,
+) -> Magic {
+    let mask = relevant_occupancy(index, directions);
+    let bits = mask.count_ones();
+    let size = 1 << bits;
+
+    let mut occupancies = Vec::with_capacity(size);
+    let mut subset: Bitboard = 0;
+    loop {
+        occupancies.push((subset, sliding_attacks(index, subset, directions)));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let offset = sliding.len();
+    sliding.resize(offset + size, 0);
+    let mut used = vec![0u32; size];
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let magic = random(seed) & random(seed) & random(seed);
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        let candidate = Magic {
+            mask,
+            magic,
+            shift: 64 - bits,
+            offset,
+        };
+        let fits = occupancies.iter().all(|&(occupied, attacks)| {
+            let slot = candidate.index(occupied) - offset;
+            if used[slot] != attempt {
+                used[slot] = attempt;
+                sliding[offset + slot] = attacks;
+                true
+            } else {
+                sliding[offset + slot] == attacks
+            }
+        });
+        if fits {
+            return candidate;
+        }
+    }
+}
+
+fn random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+fn build_tables() -> Tables {
+    let knight = step_attacks(&[
+        (1, 2),
+        (2, 1),
+        (2, -1),
+        (1, -2),
+        (-1, -2),
+        (-2, -1),
+        (-2, 1),
+        (-1, 2),
+    ]);
+    let king = step_attacks(&[
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+    ]);
+    let pawn = [
+        step_attacks(&[(-1, 1), (1, 1)]),
+        step_attacks(&[(-1, -1), (1, -1)]),
+    ];
+
+    let mut sliding = Vec::new();
+    let mut seed = 0x2F6B_1D3E_8A94_C057;
+    let rook = (0..64)
+        .map(|index| find_magic(index, &ROOK_DIRECTIONS, &mut sliding, &mut seed))
+        .collect();
+    let bishop = (0..64)
+        .map(|index| find_magic(index, &BISHOP_DIRECTIONS, &mut sliding, &mut seed))
+        .collect();
+
+    Tables {
+        knight,
+        king,
+        pawn,
+        rook,
+        bishop,
+        sliding,
+    }
+}
+
+/// The attack tables are built on first use and shared afterwards.
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(build_tables)
+}
+
+pub fn knight_attacks(index: usize) -> Bitboard {
+    tables().knight[index]
+}
+
+pub fn king_attacks(index: usize) -> Bitboard {
+    tables().king[index]
+}
+
+/// The squares a pawn of `color` on `index` captures on.
+pub fn pawn_attacks(color: TeamColor, index: usize) -> Bitboard {
+    tables().pawn[color.index()][index]
+}
+
+pub fn rook_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.sliding[tables.rook[index].index(occupied)]
+}
+
+pub fn bishop_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.sliding[tables.bishop[index].index(occupied)]
+}
+
+pub fn queen_attacks(index: usize, occupied: Bitboard) -> Bitboard {
+    rook_attacks(index, occupied) | bishop_attacks(index, occupied)
+}
+
+/// A move between square indices, small enough to be copied around freely
+/// by the search.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct BitMove {
+    pub variant: MoveType,
+    pub from: u8,
+    pub to: u8,
+}
+
+impl BitMove {
+    pub const NULL: BitMove = BitMove {
+        variant: MoveType::NonCapture,
+        from: 0,
+        to: 0,
+    };
+
+    pub fn to_move(self) -> Move {
+        Move {
+            variant: self.variant,
+            from: square_coordinates(self.from as usize),
+            to: square_coordinates(self.to as usize),
+        }
+    }
+
+    pub fn from_move(move_data: &Move) -> BitMove {
+        BitMove {
+            variant: move_data.variant,
+            from: square_index(move_data.from) as u8,
+            to: square_index(move_data.to) as u8,
+        }
+    }
+}
+
+/// More moves than any reachable position has, pseudo-legal ones included.
+pub const MAX_MOVES: usize = 256;
+
+/// A move list that lives on the stack.
+pub struct MoveList {
+    moves: [BitMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [BitMove::NULL; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, move_data: BitMove) {
+        self.moves[self.len] = move_data;
+        self.len += 1;
+    }
+
+    pub fn swap_remove(&mut self, index: usize) -> BitMove {
+        let removed = self.moves[index];
+        self.len -= 1;
+        self.moves[index] = self.moves[self.len];
+        removed
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [BitMove] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [BitMove];
+
+    fn deref(&self) -> &[BitMove] {
+        &self.moves[..self.len]
+    }
+}
+
+/// A position kept as one bitboard per piece kind and color. It is cheap to
+/// copy, so moves are made by copying it rather than undoing them later.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Position {
+    pub pieces: [[Bitboard; 6]; 2],
+    pub occupancy: [Bitboard; 2],
+    pub side_to_move: TeamColor,
+    pub is_valid_castling: [[bool; 2]; 2],
+    /// The square a pawn capturing en passant would land on.
+    pub en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+}
+
+impl Position {
+    /// Reads a position in Forsyth-Edwards Notation, see `fen::parse`.
+    pub fn from_fen(text: &str) -> Result<Position, FenError> {
+        let (board, data) = fen::parse(text)?;
+        Ok(Position::from_board(&board, &data))
+    }
+
+    pub fn from_board(board: &Board, data: &GameData) -> Position {
+        let mut position = Position {
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
+            side_to_move: data.current_turn,
+            is_valid_castling: data.is_valid_castling,
+            en_passant: data.recent_advancing_pawn.map(|(column, row)| {
+                let target_row = if row == 3 { 2 } else { 5 };
+                square_index((column, target_row))
+            }),
+            halfmove_clock: data.halfmove_clock,
+            fullmove_number: data.fullmove_number,
+            hash: 0,
+        };
+
+        for (row, squares) in board.iter().enumerate() {
+            for (column, square) in squares.iter().enumerate() {
+                if let Some(piece) = square {
+                    position.toggle_piece(*piece, square_index((column, row)));
+                }
+            }
+        }
+        position.hash = position.compute_hash();
+        position
+    }
+
+    /// The board array the renderer and the rest of the game work with.
+    pub fn to_board(&self) -> Board {
+        let mut board: Board = [[None; 8]; 8];
+        for (index, square) in board.iter_mut().flatten().enumerate() {
+            *square = self.piece_at(index);
+        }
+        board
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    pub fn pieces_of(&self, color: TeamColor, variant: PieceVariant) -> Bitboard {
+        self.pieces[color.index()][variant.index()]
+    }
+
+    pub fn piece_at(&self, index: usize) -> Option<Piece> {
+        let bit = 1 << index;
+        let color = if self.occupancy[0] & bit != 0 {
+            TeamColor::White
+        } else if self.occupancy[1] & bit != 0 {
+            TeamColor::Black
+        } else {
+            return None;
+        };
+
+        VARIANTS
+            .into_iter()
+            .find(|variant| self.pieces_of(color, *variant) & bit != 0)
+            .map(|variant| Piece { variant, color })
+    }
+
+    pub fn king_square(&self, color: TeamColor) -> usize {
+        self.pieces_of(color, PieceVariant::King).trailing_zeros() as usize
+    }
+
+    /// Adds or removes a piece, keeping the occupancy and hash up to date.
+    fn toggle_piece(&mut self, piece: Piece, index: usize) {
+        let bit = 1 << index;
+        self.pieces[piece.color.index()][piece.variant.index()] ^= bit;
+        self.occupancy[piece.color.index()] ^= bit;
+        self.hash ^= zobrist::piece_key(piece, square_coordinates(index));
+    }
+
+    /// The en passant part of the hash, which like `zobrist::en_passant_key`
+    /// only counts when the side to move can legally capture en passant.
+    fn en_passant_hash(&self) -> u64 {
+        let target = match self.en_passant {
+            Some(target) => target,
+            None => return 0,
+        };
+        let color = self.side_to_move;
+        let captured = match color {
+            TeamColor::White => target - 8,
+            TeamColor::Black => target + 8,
+        };
+        let attackers =
+            pawn_attacks(color.opposite(), target) & self.pieces_of(color, PieceVariant::Pawn);
+        let king = self.king_square(color);
+        let can_capture = squares(attackers).any(|from| {
+            let occupied = (self.occupied() ^ 1 << from ^ 1 << captured) | 1 << target;
+            let checkers = self.attackers(king, color.opposite(), occupied) & !(1 << captured);
+            checkers == 0
+        });
+        if can_capture {
+            zobrist::en_passant_file_key(target % 8)
+        } else {
+            0
+        }
+    }
+
+    /// Computes the hash from scratch. It matches `zobrist::hash` for the
+    /// same position on a `Board`.
+    pub fn compute_hash(&self) -> u64 {
+        let mut key = 0;
+        for color in [TeamColor::White, TeamColor::Black] {
+            for variant in VARIANTS {
+                for index in squares(self.pieces_of(color, variant)) {
+                    key ^= zobrist::piece_key(Piece { variant, color }, square_coordinates(index));
+                }
+            }
+        }
+
+        key ^= zobrist::castling_key(self.is_valid_castling);
+        key ^= self.en_passant_hash();
+        if self.side_to_move == TeamColor::Black {
+            key ^= zobrist::BLACK_TO_MOVE_KEY;
+        }
+        key
+    }
+
+    /// Every piece of `attacker` that attacks `index`.
+    pub fn attackers(&self, index: usize, attacker: TeamColor, occupied: Bitboard) -> Bitboard {
+        let queens = self.pieces_of(attacker, PieceVariant::Queen);
+        let rooks = self.pieces_of(attacker, PieceVariant::Castle) | queens;
+        let bishops = self.pieces_of(attacker, PieceVariant::Bishop) | queens;
+
+        (pawn_attacks(attacker.opposite(), index) & self.pieces_of(attacker, PieceVariant::Pawn))
+            | (knight_attacks(index) & self.pieces_of(attacker, PieceVariant::Knight))
+            | (king_attacks(index) & self.pieces_of(attacker, PieceVariant::King))
+            | (rook_attacks(index, occupied) & rooks)
+            | (bishop_attacks(index, occupied) & bishops)
+    }
+
+    pub fn is_square_attacked(&self, index: usize, attacker: TeamColor) -> bool {
+        self.attackers(index, attacker, self.occupied()) != 0
+    }
+
+    pub fn is_in_check(&self, color: TeamColor) -> bool {
+        self.is_square_attacked(self.king_square(color), color.opposite())
+    }
+
+    fn push_pawn_moves(&self, moves: &mut MoveList, from: usize, to: usize, variant: MoveType) {
+        let promotion_row = match self.side_to_move {
+            TeamColor::White => 7,
+            TeamColor::Black => 0,
+        };
+        if to / 8 == promotion_row {
+            for promoted in [
+                PieceVariant::Queen,
+                PieceVariant::Castle,
+                PieceVariant::Bishop,
+                PieceVariant::Knight,
+            ] {
+                moves.push(BitMove {
+                    variant: MoveType::Promotion(promoted),
+                    from: from as u8,
+                    to: to as u8,
+                });
+            }
+        } else {
+            moves.push(BitMove {
+                variant,
+                from: from as u8,
+                to: to as u8,
+            });
+        }
+    }
+
+    /// With `captures_only`, pushes are left out unless they promote.
+    fn generate_pawn_moves(&self, moves: &mut MoveList, captures_only: bool) {
+        let color = self.side_to_move;
+        let empty = !self.occupied();
+        let enemies = self.occupancy[color.opposite().index()];
+        let (forward, start_row, promotion_row): (i32, usize, usize) = match color {
+            TeamColor::White => (8, 1, 7),
+            TeamColor::Black => (-8, 6, 0),
+        };
+
+        for from in squares(self.pieces_of(color, PieceVariant::Pawn)) {
+            let one_step = (from as i32 + forward) as usize;
+            let is_push_wanted = !captures_only || one_step / 8 == promotion_row;
+            if empty & (1 << one_step) != 0 && is_push_wanted {
+                self.push_pawn_moves(moves, from, one_step, MoveType::NonCapture);
+
+                let two_steps = (one_step as i32 + forward) as usize;
+                if !captures_only && from / 8 == start_row && empty & (1 << two_steps) != 0 {
+                    moves.push(BitMove {
+                        variant: MoveType::AdvancePawn,
+                        from: from as u8,
+                        to: two_steps as u8,
+                    });
+                }
+            }
+
+            let attacks = pawn_attacks(color, from);
+            for to in squares(attacks & enemies) {
+                self.push_pawn_moves(moves, from, to, MoveType::Capture);
+            }
+            if let Some(target) = self.en_passant {
+                if attacks & (1 << target) != 0 {
+                    moves.push(BitMove {
+                        variant: MoveType::EnPassant,
+                        from: from as u8,
+                        to: target as u8,
+                    });
+                }
+            }
+        }
+    }
+
+    fn generate_castling_moves(&self, moves: &mut MoveList) {
+        let color = self.side_to_move;
+        let rights = self.is_valid_castling[color.index()];
+        let home = color.home_row() * 8;
+        let king = home + 4;
+        if self.pieces_of(color, PieceVariant::King) & (1 << king) == 0 {
+            return;
+        }
+
+        let rooks = self.pieces_of(color, PieceVariant::Castle);
+        // (rook column, squares to be empty, squares the king crosses)
+        for (side, (rook_column, between, path)) in [
+            (0, 0b0000_1110u64, [4, 3, 2]),
+            (7, 0b0110_0000u64, [4, 5, 6]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if !rights[side]
+                || rooks & (1 << (home + rook_column)) == 0
+                || self.occupied() & (between << home) != 0
+                || path
+                    .iter()
+                    .any(|column| self.is_square_attacked(home + column, color.opposite()))
+            {
+                continue;
+            }
+            moves.push(BitMove {
+                variant: MoveType::Castling(rook_column),
+                from: king as u8,
+                to: (home + path[2]) as u8,
+            });
+        }
+    }
+
+    /// Moves that follow the piece rules but may leave the own king in check.
+    pub fn generate_pseudo_legal_moves(&self, moves: &mut MoveList) {
+        self.generate_pseudo_legal(moves, false);
+    }
+
+    fn generate_pseudo_legal(&self, moves: &mut MoveList, captures_only: bool) {
+        let color = self.side_to_move;
+        let own = self.occupancy[color.index()];
+        let enemies = self.occupancy[color.opposite().index()];
+        let occupied = self.occupied();
+        let targets = if captures_only { enemies } else { !own };
+
+        self.generate_pawn_moves(moves, captures_only);
+        for variant in [
+            PieceVariant::Knight,
+            PieceVariant::Bishop,
+            PieceVariant::Castle,
+            PieceVariant::Queen,
+            PieceVariant::King,
+        ] {
+            for from in squares(self.pieces_of(color, variant)) {
+                let attacks = match variant {
+                    PieceVariant::Knight => knight_attacks(from),
+                    PieceVariant::Bishop => bishop_attacks(from, occupied),
+                    PieceVariant::Castle => rook_attacks(from, occupied),
+                    PieceVariant::Queen => queen_attacks(from, occupied),
+                    _ => king_attacks(from),
+                };
+                for to in squares(attacks & targets) {
+                    let variant = if enemies & (1 << to) != 0 {
+                        MoveType::Capture
+                    } else {
+                        MoveType::NonCapture
+                    };
+                    moves.push(BitMove {
+                        variant,
+                        from: from as u8,
+                        to: to as u8,
+                    });
+                }
+            }
+        }
+        if !captures_only {
+            self.generate_castling_moves(moves);
+        }
+    }
+
+    /// Whether the pseudo-legal `move_data` keeps the own king out of check.
+    pub fn is_legal(&self, move_data: BitMove) -> bool {
+        let next = self.make_move(move_data);
+        !next.is_in_check(self.side_to_move)
+    }
+
+    pub fn generate_legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.generate_pseudo_legal_moves(&mut moves);
+        self.retain_legal(&mut moves);
+        moves
+    }
+
+    /// The legal captures and promotions, for the quiescence search.
+    pub fn generate_legal_captures(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.generate_pseudo_legal(&mut moves, true);
+        self.retain_legal(&mut moves);
+        moves
+    }
+
+    fn retain_legal(&self, moves: &mut MoveList) {
+        let mut i = 0;
+        while i < moves.len() {
+            if self.is_legal(moves[i]) {
+                i += 1;
+            } else {
+                moves.swap_remove(i);
+            }
+        }
+    }
+
+    /// Plays a pseudo-legal move on a copy of the position, updating castling
+    /// rights, en passant, clocks and the hash incrementally.
+    pub fn make_move(&self, move_data: BitMove) -> Position {
+        let mut next = *self;
+        let color = self.side_to_move;
+        let (from, to) = (move_data.from as usize, move_data.to as usize);
+        let piece = match self.piece_at(from) {
+            Some(piece) => piece,
+            None => return next,
+        };
+
+        next.hash ^= zobrist::castling_key(self.is_valid_castling) ^ self.en_passant_hash();
+
+        let captured_square = match move_data.variant {
+            MoveType::EnPassant => Some(to ^ 8),
+            MoveType::Castling(_) => None,
+            _ => Some(to),
+        };
+        let captured = captured_square.and_then(|index| Some((self.piece_at(index)?, index)));
+        if let Some((captured, index)) = captured {
+            next.toggle_piece(captured, index);
+        }
+
+        next.toggle_piece(piece, from);
+        let placed = match move_data.variant {
+            MoveType::Promotion(variant) => Piece { variant, color },
+            _ => piece,
+        };
+        next.toggle_piece(placed, to);
+
+        if let MoveType::Castling(rook_column) = move_data.variant {
+            let home = color.home_row() * 8;
+            let rook_target = if rook_column == 0 { 3 } else { 5 };
+            let rook = Piece {
+                variant: PieceVariant::Castle,
+                color,
+            };
+            next.toggle_piece(rook, home + rook_column);
+            next.toggle_piece(rook, home + rook_target);
+        }
+
+        if piece.variant == PieceVariant::King {
+            next.is_valid_castling[color.index()] = [false; 2];
+        }
+        for index in [from, to] {
+            for owner in [TeamColor::White, TeamColor::Black] {
+                let home = owner.home_row() * 8;
+                if index == home {
+                    next.is_valid_castling[owner.index()][0] = false;
+                } else if index == home + 7 {
+                    next.is_valid_castling[owner.index()][1] = false;
+                }
+            }
+        }
+
+        next.en_passant = match move_data.variant {
+            MoveType::AdvancePawn => Some((from + to) / 2),
+            _ => None,
+        };
+        if piece.variant == PieceVariant::Pawn || captured.is_some() {
+            next.halfmove_clock = 0;
+        } else {
+            next.halfmove_clock += 1;
+        }
+        if color == TeamColor::Black {
+            next.fullmove_number += 1;
+        }
+        next.side_to_move = color.opposite();
+
+        next.hash ^= zobrist::BLACK_TO_MOVE_KEY;
+        next.hash ^= zobrist::castling_key(next.is_valid_castling) ^ next.en_passant_hash();
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+    use crate::produce;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn magic_attacks_match_ray_walk() {
+        let mut seed = 0x1234_5678_9ABC_DEF1;
+        for index in 0..64 {
+            for _ in 0..64 {
+                let occupied = random(&mut seed) & random(&mut seed);
+                assert_eq!(
+                    rook_attacks(index, occupied),
+                    sliding_attacks(index, occupied, &ROOK_DIRECTIONS)
+                );
+                assert_eq!(
+                    bishop_attacks(index, occupied),
+                    sliding_attacks(index, occupied, &BISHOP_DIRECTIONS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn board_round_trip() {
+        let (board, data) = fen::parse(KIWIPETE).unwrap();
+        let position = Position::from_board(&board, &data);
+
+        assert_eq!(position.to_board(), board);
+        assert_eq!(position.hash, data.hash);
+    }
+
+    /// Both generators must agree move for move, and the incremental hash
+    /// must agree with the one kept on the board.
+    fn assert_same_moves(board: &mut Board, data: &mut GameData, depth: u32) {
+        let position = Position::from_board(board, data);
+        let mut expected: Vec<Move> = produce::generate_legal_moves(board, data)
+            .into_iter()
+            .collect();
+        let mut found: Vec<Move> = position
+            .generate_legal_moves()
+            .iter()
+            .map(|move_data| move_data.to_move())
+            .collect();
+        expected.sort_by_key(|move_data| format!("{:?}", move_data));
+        found.sort_by_key(|move_data| format!("{:?}", move_data));
+        assert_eq!(found, expected, "{}", fen::serialize(board, data));
+        if depth == 0 {
+            return;
+        }
+
+        for move_data in expected {
+            let next = position.make_move(BitMove::from_move(&move_data));
+            let undo = produce::make_move(board, data, &move_data);
+            assert_eq!(next.hash, data.hash, "{}", fen::serialize(board, data));
+            assert_eq!(next.hash, next.compute_hash());
+            assert_eq!(next.to_board(), *board);
+            assert_same_moves(board, data, depth - 1);
+            produce::unmake_move(board, data, &undo);
+        }
+    }
+
+    #[test]
+    fn captures_are_the_tactical_part_of_all_moves() {
+        for position in [
+            KIWIPETE,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let position = Position::from_fen(position).unwrap();
+            let mut expected: Vec<BitMove> = position
+                .generate_legal_moves()
+                .iter()
+                .filter(|move_data| {
+                    matches!(
+                        move_data.variant,
+                        MoveType::Capture | MoveType::EnPassant | MoveType::Promotion(_)
+                    )
+                })
+                .copied()
+                .collect();
+            let mut found = position.generate_legal_captures().to_vec();
+            found.sort_by_key(|move_data| format!("{:?}", move_data));
+            expected.sort_by_key(|move_data| format!("{:?}", move_data));
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn generators_agree() {
+        for position in [
+            fen::STARTING_POSITION,
+            KIWIPETE,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let (mut board, mut data) = fen::parse(position).unwrap();
+            assert_same_moves(&mut board, &mut data, 2);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+/// Kept back from every allocation for the time it takes to hand the move
+/// over once the search is done.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+/// How many more moves a game is assumed to last when the time control
+/// does not say.
+const EXPECTED_MOVES_TO_GO: u32 = 25;
+
+/// The clock of the side to move.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeControl {
+    pub remaining: Duration,
+    /// Time added to the clock after every move.
+    pub increment: Duration,
+    /// Moves left until the next time control, if there is one.
+    pub moves_to_go: Option<u32>,
+}
+
+/// How long one search may take.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeBudget {
+    /// No new iteration is started once this much time has passed.
+    pub soft: Duration,
+    /// The search is aborted when this much time has passed.
+    pub hard: Duration,
+}
+
+impl TimeBudget {
+    /// A budget for a fixed time per move.
+    pub fn fixed(time: Duration) -> TimeBudget {
+        TimeBudget {
+            soft: time,
+            hard: time,
+        }
+    }
+}
+
+/// Splits the remaining time evenly over the moves expected to come, adds
+/// most of the increment, and lets a search overrun that share a few times
+/// over as long as a good part of the clock stays untouched.
+pub fn allocate(control: &TimeControl) -> TimeBudget {
+    let usable = control.remaining.saturating_sub(MOVE_OVERHEAD);
+    let moves_to_go = control
+        .moves_to_go
+        .unwrap_or(EXPECTED_MOVES_TO_GO)
+        .clamp(1, EXPECTED_MOVES_TO_GO);
+
+    let share = usable / moves_to_go + control.increment * 3 / 4;
+    let hard = (share * 3)
+        .min(usable / 2 + control.increment / 2)
+        .min(usable);
+    TimeBudget {
+        soft: share.min(hard),
+        hard,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(remaining_ms: u64, increment_ms: u64, moves_to_go: Option<u32>) -> TimeControl {
+        TimeControl {
+            remaining: Duration::from_millis(remaining_ms),
+            increment: Duration::from_millis(increment_ms),
+            moves_to_go,
+        }
+    }
+
+    #[test]
+    fn never_spends_the_whole_clock() {
+        for (remaining, increment, moves_to_go) in [
+            (60_000, 0, None),
+            (60_000, 1_000, None),
+            (1_000, 5_000, None),
+            (10, 0, None),
+            (30_000, 0, Some(1)),
+        ] {
+            let budget = allocate(&control(remaining, increment, moves_to_go));
+            assert!(budget.soft <= budget.hard);
+            assert!(budget.hard < Duration::from_millis(remaining).max(MOVE_OVERHEAD));
+        }
+    }
+
+    #[test]
+    fn increment_and_fewer_moves_mean_more_time() {
+        let base = allocate(&control(60_000, 0, None));
+        assert!(allocate(&control(60_000, 2_000, None)).soft > base.soft);
+        assert!(allocate(&control(60_000, 0, Some(5))).soft > base.soft);
+        assert_eq!(base.soft, Duration::from_millis(59_970) / 25);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::bitboard::{BitMove, Position};
+use crate::clock::{self, TimeBudget, TimeControl};
+use crate::game::{Game, TeamColor};
+use crate::parameters::Parameters;
+use crate::polyglot::Book;
+use crate::search::{self, SearchOptions};
+use crate::syzygy::Tablebases;
+use crate::transposition::{self, TranspositionTable};
+use crate::Command;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The last move number on which the computer looks in its opening book.
+const BOOK_MOVES: u32 = 12;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Casual,
+    Intermediate,
+    Advanced,
+    Strong,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Casual,
+        Difficulty::Intermediate,
+        Difficulty::Advanced,
+        Difficulty::Strong,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Casual => "Casual",
+            Difficulty::Intermediate => "Intermediate",
+            Difficulty::Advanced => "Advanced",
+            Difficulty::Strong => "Strong",
+        }
+    }
+
+    /// How many plies the computer looks ahead at most.
+    pub fn depth(self) -> u32 {
+        match self {
+            Difficulty::Beginner => 1,
+            Difficulty::Casual => 2,
+            Difficulty::Intermediate => 3,
+            Difficulty::Advanced => 4,
+            Difficulty::Strong => 6,
+        }
+    }
+
+    /// The time on the computer's clock at the start of a game.
+    pub fn starting_time(self) -> Duration {
+        match self {
+            Difficulty::Beginner => Duration::from_secs(60),
+            Difficulty::Casual => Duration::from_secs(2 * 60),
+            Difficulty::Intermediate => Duration::from_secs(3 * 60),
+            Difficulty::Advanced => Duration::from_secs(5 * 60),
+            Difficulty::Strong => Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// The time added to the computer's clock after each of its moves.
+    pub fn increment(self) -> Duration {
+        match self {
+            Difficulty::Beginner => Duration::from_secs(1),
+            Difficulty::Casual => Duration::from_secs(1),
+            Difficulty::Intermediate => Duration::from_secs(2),
+            Difficulty::Advanced => Duration::from_secs(3),
+            Difficulty::Strong => Duration::from_secs(5),
+        }
+    }
+
+    /// Largest error in centipawns added to each evaluation.
+    pub fn evaluation_noise(self) -> i32 {
+        match self {
+            Difficulty::Beginner => 150,
+            Difficulty::Casual => 80,
+            Difficulty::Intermediate => 30,
+            Difficulty::Advanced => 10,
+            Difficulty::Strong => 0,
+        }
+    }
+
+    /// The chance of playing a random move instead of the one searched.
+    pub fn blunder_probability(self) -> f64 {
+        match self {
+            Difficulty::Beginner => 0.25,
+            Difficulty::Casual => 0.1,
+            Difficulty::Intermediate => 0.03,
+            Difficulty::Advanced => 0.0,
+            Difficulty::Strong => 0.0,
+        }
+    }
+}
+
+/// The computer opponent, playing the side the local player did not pick.
+pub struct Computer {
+    pub color: TeamColor,
+    pub difficulty: Difficulty,
+    /// Thinking time left on the computer's clock.
+    pub clock: Duration,
+    /// Shared with the search thread and kept from move to move.
+    table: Arc<Mutex<TranspositionTable>>,
+    thinking: Option<Thinking>,
+}
+
+/// A search running on its own thread so the event loop keeps going.
+struct Thinking {
+    /// Key of the position being searched, to drop the answer if the game
+    /// moved on in the meantime, e.g. through undo.
+    hash: u64,
+    started: Instant,
+    receiver: Receiver<Option<BitMove>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Thinking {
+    /// A search nobody waits for any more gives up the transposition table
+    /// right away instead of when its time runs out.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Computer {
+    /// A computer with a transposition table of about `megabytes`.
+    pub fn new(megabytes: usize) -> Computer {
+        Computer {
+            color: TeamColor::Black,
+            difficulty: Difficulty::Intermediate,
+            clock: Difficulty::Intermediate.starting_time(),
+            table: Arc::new(Mutex::new(TranspositionTable::new(megabytes))),
+            thinking: None,
+        }
+    }
+
+    /// Winds the clock back to the start for a new game.
+    pub fn reset_clock(&mut self) {
+        self.clock = self.difficulty.starting_time();
+    }
+
+    fn time_control(&self) -> TimeControl {
+        TimeControl {
+            remaining: self.clock,
+            increment: self.difficulty.increment(),
+            moves_to_go: None,
+        }
+    }
+}
+
+impl Default for Computer {
+    fn default() -> Computer {
+        Computer::new(transposition::DEFAULT_MEGABYTES)
+    }
+}
+
+/// A xorshift generator seeded from the clock; the computer only needs its
+/// choices to differ between games, not to be unpredictable.
+struct Random(u64);
+
+impl Random {
+    fn from_clock() -> Random {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Random(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0.0..1.0`.
+    fn next_fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Plays from `book` in the opening and otherwise searches `position`
+/// within the limits of `difficulty`, now and then throwing the result away
+/// for a random move.
+#[allow(clippy::too_many_arguments)]
+fn choose_move(
+    position: &Position,
+    history: &[u64],
+    difficulty: Difficulty,
+    budget: TimeBudget,
+    parameters: Arc<Parameters>,
+    book: Option<&Book>,
+    tablebases: Option<Arc<Tablebases>>,
+    stop: Arc<AtomicBool>,
+    table: &mut TranspositionTable,
+) -> Option<BitMove> {
+    let mut random = Random::from_clock();
+    if position.fullmove_number <= BOOK_MOVES {
+        let book_move = book.and_then(|book| book.choose_move(position, random.next_fraction()));
+        if book_move.is_some() {
+            return book_move;
+        }
+    }
+
+    let options = SearchOptions {
+        depth: difficulty.depth(),
+        time: Some(budget),
+        evaluation_noise: difficulty.evaluation_noise(),
+        seed: random.next(),
+        parameters,
+        tablebases,
+        stop: Some(stop),
+    };
+    let result = search::search(position, history, &options, table);
+
+    if random.next_fraction() < difficulty.blunder_probability() {
+        let moves = position.generate_legal_moves();
+        if !moves.is_empty() {
+            return Some(moves[random.next() as usize % moves.len()]);
+        }
+    }
+    result.best_move
+}
+
+fn start_thinking(game: &Game, computer: &Computer) -> Thinking {
+    let difficulty = computer.difficulty;
+    let table = Arc::clone(&computer.table);
+    let parameters = Arc::clone(&game.parameters);
+    let book = game.book.clone();
+    let tablebases = game.tablebases.clone();
+    let budget = clock::allocate(&computer.time_control());
+    let data = &game.cache.data;
+    let position = Position::from_board(&game.board, data);
+    let history = data.position_history[..data.position_history.len().saturating_sub(1)].to_vec();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let search_stop = Arc::clone(&stop);
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // a search left over from before an undo has been told to stop and
+        // lets go of the table soon
+        let mut table = table
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let best_move = choose_move(
+            &position,
+            &history,
+            difficulty,
+            budget,
+            parameters,
+            book.as_deref(),
+            tablebases,
+            search_stop,
+            &mut table,
+        );
+        // the game may have dropped the receiver already, which is fine
+        let _ = sender.send(best_move);
+    });
+
+    Thinking {
+        hash: data.hash,
+        started: Instant::now(),
+        receiver,
+        stop,
+    }
+}
+
+/// Checked once per frame by the event loop. Starts a search when it is the
+/// computer's turn and hands its move over as the same commands a click on
+/// the board would produce.
+pub fn handle_turn(game: &mut Game) -> Vec<Command> {
+    let is_computer_turn = game.is_computer_turn();
+    let mut computer = match game.computer.take() {
+        Some(computer) => computer,
+        None => return vec![Command::Idle],
+    };
+    let thinking = computer.thinking.take();
+    let commands = if is_computer_turn {
+        let thinking = match thinking {
+            Some(thinking) if thinking.hash == game.cache.data.hash => thinking,
+            _ => start_thinking(game, &computer),
+        };
+        match thinking.receiver.try_recv() {
+            Ok(Some(move_data)) => {
+                computer.clock = computer.clock.saturating_sub(thinking.started.elapsed())
+                    + computer.difficulty.increment();
+                vec![
+                    Command::Unfocus,
+                    Command::Move(move_data.to_move()),
+                    Command::ChangeTurn,
+                ]
+            }
+            Ok(None) | Err(TryRecvError::Disconnected) => vec![Command::Idle],
+            Err(TryRecvError::Empty) => {
+                computer.thinking = Some(thinking);
+                vec![Command::Idle]
+            }
+        }
+    } else {
+        vec![Command::Idle]
+    };
+
+    game.computer = Some(computer);
+    commands
+}
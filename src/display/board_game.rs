@@ -11,6 +11,66 @@ pub fn render(
     configuration: &Config,
     game: &Game,
     textures: &Textures,
+) -> Result<(), Error> {
+    render_board(canvas, configuration, game, textures)?;
+
+    let undo_rect = Rect::new(
+        5,
+        (game.cache.board_offset.1 + game.cache.board_size.1 / 2.0 - 20.0) as i32,
+        (game.cache.board_offset.0 - 10.0) as u32,
+        40,
+    );
+    let redo_rect = Rect::new(
+        (game.cache.board_offset.0 + game.cache.board_size.0 + 5.0) as i32,
+        (game.cache.board_offset.1 + game.cache.board_size.1 / 2.0 - 20.0) as i32,
+        (game.cache.board_offset.0 - 10.0) as u32,
+        40,
+    );
+    if !game.undo_stack.is_empty() {
+        super::render_graphical_text(canvas, game, configuration, undo_rect, "Undo")?;
+    }
+    if !game.redo_stack.is_empty() {
+        super::render_graphical_text(canvas, game, configuration, redo_rect, "Redo")?;
+    }
+
+    let hint = if !game.cache.data.typed_move.is_empty() {
+        Some(game.cache.data.typed_move.clone())
+    } else if let Some(ref analysis) = game.cache.analysis {
+        let text = match analysis.verdict {
+            Some(verdict) => verdict.to_string(),
+            None if analysis.probe.is_some() => "Probing the tablebases".to_string(),
+            None => "Not in the tablebases".to_string(),
+        };
+        Some(text)
+    } else if game.cache.data.claimable_draw().is_some() {
+        Some("ctrl+D to claim a draw".to_string())
+    } else {
+        game.computer
+            .as_ref()
+            .map(|computer| format!("Computer: {}", computer.difficulty.name()))
+    };
+
+    if let Some(ref text) = hint {
+        let text_width = game.cache.square_size.0 * text.len() as f32 * 0.3;
+        let text_height = game.cache.window_size.1 - game.cache.board_size.1 - 40.0;
+        let text_rect = Rect::new(
+            ((game.cache.window_size.0 - text_width) / 2.0) as i32,
+            (game.cache.board_offset.1 + game.cache.board_size.1 + 10.0) as i32,
+            text_width as u32,
+            text_height as u32,
+        );
+        super::render_graphical_text(canvas, game, configuration, text_rect, text)?;
+    }
+
+    canvas.present();
+    Ok(())
+}
+
+pub fn render_board(
+    canvas: &mut WindowCanvas,
+    configuration: &Config,
+    game: &Game,
+    textures: &Textures,
 ) -> Result<(), Error> {
     super::render_canvas_background(canvas, &configuration.palette)?;
 
@@ -43,7 +103,8 @@ pub fn render(
                 (game.cache.square_size.1 - 2.0 * padding) as u32,
             );
 
-            if column % 2 != 0 && row % 2 == 0 || column % 2 == 0 && row % 2 != 0 {
+            // a1 is a dark square
+            if (column + row) % 2 == 0 {
                 canvas.set_draw_color(configuration.palette.default_dark_color);
                 canvas.fill_rect(cell_rect)?;
             }
@@ -62,14 +123,19 @@ pub fn render(
         }
     }
 
-    canvas.present();
     Ok(())
 }
 
 fn into_absolute_position(game: &Game, pos: (usize, usize)) -> (u32, u32) {
+    // the board is stored with White at row 0, flip it to the player's side
+    let (column, row) = match game.cache.data.player_color {
+        TeamColor::White => (pos.0, 7 - pos.1),
+        TeamColor::Black => (7 - pos.0, pos.1),
+    };
+
     (
-        (pos.0 as u32 * game.cache.square_size.0 as u32) + game.cache.board_offset.0 as u32,
-        (pos.1 as u32 * game.cache.square_size.1 as u32) + game.cache.board_offset.1 as u32,
+        (column as u32 * game.cache.square_size.0 as u32) + game.cache.board_offset.0 as u32,
+        (row as u32 * game.cache.square_size.1 as u32) + game.cache.board_offset.1 as u32,
     )
 }
 
@@ -0,0 +1,38 @@
+use crate::computer::Difficulty;
+use crate::config::Config;
+use crate::game::Game;
+use crate::Error;
+
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+pub fn render(canvas: &mut WindowCanvas, configuration: &Config, game: &Game) -> Result<(), Error> {
+    let window_size = game.cache.window_size;
+    let text_width = window_size.0 * 0.45;
+    let text_height = window_size.1 * 0.12;
+
+    let text_rect = Rect::new(
+        ((window_size.0 - text_width) / 2.0) as i32,
+        (window_size.1 * 0.05) as i32,
+        text_width as u32,
+        text_height as u32,
+    );
+
+    super::render_canvas_background(canvas, &configuration.palette)?;
+    super::render_graphical_text(canvas, game, configuration, text_rect, "Select Difficulty")?;
+
+    let button_width = window_size.0 * 0.40;
+    let button_height = window_size.1 * 0.10;
+    for (i, difficulty) in Difficulty::ALL.iter().enumerate() {
+        let button_rect = Rect::new(
+            ((window_size.0 - button_width) / 2.0) as i32,
+            (window_size.1 * 0.22 + i as f32 * button_height * 1.4) as i32,
+            button_width as u32,
+            button_height as u32,
+        );
+        super::render_graphical_text(canvas, game, configuration, button_rect, difficulty.name())?;
+    }
+
+    canvas.present();
+    Ok(())
+}
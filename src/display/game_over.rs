@@ -0,0 +1,97 @@
+use crate::config::Config;
+use crate::game::{Game, GameOverReason, GameResult};
+use crate::Error;
+use crate::Textures;
+
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+pub fn render(
+    canvas: &mut WindowCanvas,
+    configuration: &Config,
+    game: &Game,
+    textures: &Textures,
+    result: GameResult,
+    reason: GameOverReason,
+) -> Result<(), Error> {
+    super::board_game::render_board(canvas, configuration, game, textures)?;
+
+    let board_size = game.cache.board_size;
+    let board_offset = game.cache.board_offset;
+    let margin_offset = 10.0;
+
+    let main_rect = Rect::new(
+        (board_offset.0 + board_size.0 * 0.1) as i32,
+        (board_offset.1 + board_size.1 * 0.25) as i32,
+        (board_size.0 * 0.8) as u32,
+        (board_size.1 * 0.5) as u32,
+    );
+    canvas.set_draw_color(configuration.palette.default_light_color);
+    canvas.fill_rect(main_rect)?;
+    canvas.set_draw_color(configuration.palette.default_dark_color);
+    canvas.draw_rect(main_rect)?;
+
+    let result_text = match result {
+        GameResult::WhiteWins => "White wins",
+        GameResult::BlackWins => "Black wins",
+        GameResult::Draw => "Draw",
+    };
+    let reason_text = match reason {
+        GameOverReason::Checkmate => "by checkmate",
+        GameOverReason::Stalemate => "by stalemate",
+        GameOverReason::Resignation => "by resignation",
+        GameOverReason::FiftyMoveRule => "by the fifty-move rule",
+        GameOverReason::SeventyFiveMoveRule => "by the seventy-five-move rule",
+        GameOverReason::ThreefoldRepetition => "by threefold repetition",
+        GameOverReason::FivefoldRepetition => "by fivefold repetition",
+        GameOverReason::InsufficientMaterial => "by insufficient material",
+    };
+
+    let text_width = main_rect.width() as f32 * 0.6;
+    let text_height = main_rect.height() as f32 * 0.25;
+    let result_rect = Rect::new(
+        main_rect.x() + ((main_rect.width() as f32 - text_width) / 2.0) as i32,
+        main_rect.y() + margin_offset as i32,
+        text_width as u32,
+        text_height as u32,
+    );
+    let reason_rect = Rect::new(
+        result_rect.x(),
+        result_rect.y() + text_height as i32,
+        text_width as u32,
+        (text_height * 0.8) as u32,
+    );
+
+    super::render_graphical_text(canvas, game, configuration, result_rect, result_text)?;
+    super::render_graphical_text(canvas, game, configuration, reason_rect, reason_text)?;
+
+    let (rematch_rect, menu_rect) = get_button_rects(game);
+    super::render_graphical_text(canvas, game, configuration, rematch_rect, "Rematch")?;
+    super::render_graphical_text(canvas, game, configuration, menu_rect, "Menu")?;
+
+    canvas.present();
+    Ok(())
+}
+
+pub fn get_button_rects(game: &Game) -> (Rect, Rect) {
+    let board_size = game.cache.board_size;
+    let board_offset = game.cache.board_offset;
+    let button_width = board_size.0 * 0.3;
+    let button_height = board_size.1 * 0.12;
+    let y = board_offset.1 + board_size.1 * 0.75 - button_height - 10.0;
+
+    let rematch_rect = Rect::new(
+        (board_offset.0 + board_size.0 * 0.15) as i32,
+        y as i32,
+        button_width as u32,
+        button_height as u32,
+    );
+    let menu_rect = Rect::new(
+        (board_offset.0 + board_size.0 * 0.85 - button_width) as i32,
+        y as i32,
+        button_width as u32,
+        button_height as u32,
+    );
+
+    (rematch_rect, menu_rect)
+}
@@ -1,5 +1,8 @@
 mod board_game;
+mod difficulty_selection;
+pub mod game_over;
 mod promote_selection;
+mod replay;
 mod start_menu;
 mod team_selection;
 
@@ -45,7 +48,12 @@ pub fn render(
     match game.state {
         StartMenu => start_menu::render(canvas, configuration, game),
         TeamSelection => team_selection::render(canvas, configuration, game),
+        DifficultySelection => difficulty_selection::render(canvas, configuration, game),
         BoardGame => board_game::render(canvas, configuration, game, textures),
         PromoteSelection => promote_selection::render(canvas, configuration, game, textures),
+        GameOver(result, reason) => {
+            game_over::render(canvas, configuration, game, textures, result, reason)
+        }
+        Replay => replay::render(canvas, configuration, game, textures),
     }
 }
@@ -0,0 +1,41 @@
+use crate::config::Config;
+use crate::game::Game;
+use crate::Error;
+use crate::Textures;
+
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+pub fn render(
+    canvas: &mut WindowCanvas,
+    configuration: &Config,
+    game: &Game,
+    textures: &Textures,
+) -> Result<(), Error> {
+    super::board_game::render_board(canvas, configuration, game, textures)?;
+
+    if let Some(ref replay) = game.replay {
+        let mut text = format!(
+            "game {} of {}   move {} of {}",
+            replay.game_index + 1,
+            replay.games.len(),
+            replay.ply,
+            replay.positions.len().saturating_sub(1)
+        );
+        if let Some(ref error) = replay.error {
+            text.push_str(&format!("   ({})", error));
+        }
+        let text_width = game.cache.board_size.0 * 0.7;
+        let text_height = game.cache.window_size.1 - game.cache.board_size.1 - 40.0;
+        let text_rect = Rect::new(
+            ((game.cache.window_size.0 - text_width) / 2.0) as i32,
+            (game.cache.board_offset.1 + game.cache.board_size.1 + 10.0) as i32,
+            text_width as u32,
+            text_height as u32,
+        );
+        super::render_graphical_text(canvas, game, configuration, text_rect, &text)?;
+    }
+
+    canvas.present();
+    Ok(())
+}
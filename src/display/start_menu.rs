@@ -22,16 +22,23 @@ pub fn render(canvas: &mut WindowCanvas, configuration: &Config, game: &Game) ->
         button_width as u32,
         button_height as u32,
     );
-    let quit_rect = Rect::new(
+    let computer_rect = Rect::new(
         ((window_size.0 - button_width) / 2.0) as i32,
         ((window_size.1 - button_height) / 2.0 + button_height * 1.2) as i32,
         button_width as u32,
         button_height as u32,
     );
+    let quit_rect = Rect::new(
+        ((window_size.0 - button_width) / 2.0) as i32,
+        ((window_size.1 - button_height) / 2.0 + button_height * 2.4) as i32,
+        button_width as u32,
+        button_height as u32,
+    );
 
     super::render_canvas_background(canvas, &configuration.palette)?;
     super::render_graphical_text(canvas, game, configuration, chess_rect, "CHESS")?;
     super::render_graphical_text(canvas, game, configuration, play_rect, "Play")?;
+    super::render_graphical_text(canvas, game, configuration, computer_rect, "Computer")?;
     super::render_graphical_text(canvas, game, configuration, quit_rect, "Quit")?;
 
     canvas.present();
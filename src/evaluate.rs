@@ -0,0 +1,208 @@
+use crate::bitboard::{self, Bitboard, Position, VARIANTS};
+use crate::game::{PieceVariant, TeamColor};
+use crate::parameters::{Parameters, ENDGAME, MIDGAME};
+
+/// Piece values in centipawns, in the order of `PieceVariant::index`, for
+/// weighing up exchanges.
+pub const PIECE_VALUES: [i32; 6] = [0, 900, 500, 330, 320, 100];
+
+/// How much each piece on the board counts towards the midgame, by
+/// `PieceVariant::index`.
+const PHASE_WEIGHTS: [i32; 6] = [0, 4, 2, 1, 1, 0];
+/// The phase of the starting position; the endgame is at 0.
+const OPENING_PHASE: i32 = 24;
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+fn file(column: usize) -> Bitboard {
+    FILE_A << column
+}
+
+fn neighbouring_files(column: usize) -> Bitboard {
+    let left = if column > 0 { file(column - 1) } else { 0 };
+    let right = if column < 7 { file(column + 1) } else { 0 };
+    left | right
+}
+
+/// The squares on the ranks in front of `row` as seen by `color`.
+fn ranks_ahead(color: TeamColor, row: usize) -> Bitboard {
+    match color {
+        TeamColor::White if row < 7 => !0 << (8 * (row + 1)),
+        TeamColor::Black => (1 << (8 * row)) - 1,
+        TeamColor::White => 0,
+    }
+}
+
+/// The rank of `row` counted from the side of `color`, 0 for its home row.
+fn relative_rank(color: TeamColor, row: usize) -> usize {
+    match color {
+        TeamColor::White => row,
+        TeamColor::Black => 7 - row,
+    }
+}
+
+/// Where to look up `index` in a piece-square table, which has rank 8 first
+/// and is written for White.
+fn table_square(color: TeamColor, index: usize) -> usize {
+    match color {
+        TeamColor::White => index ^ 56,
+        TeamColor::Black => index,
+    }
+}
+
+fn add(score: &mut [i32; 2], weight: impl Fn(usize) -> i32) {
+    for phase in [MIDGAME, ENDGAME] {
+        score[phase] += weight(phase);
+    }
+}
+
+/// How far the position is from the endgame, from `OPENING_PHASE` with all
+/// pieces on the board down to 0 with only kings and pawns left.
+fn game_phase(position: &Position) -> i32 {
+    let phase: i32 = VARIANTS
+        .iter()
+        .map(|variant| {
+            let count = (position.pieces_of(TeamColor::White, *variant)
+                | position.pieces_of(TeamColor::Black, *variant))
+            .count_ones() as i32;
+            count * PHASE_WEIGHTS[variant.index()]
+        })
+        .sum();
+    phase.min(OPENING_PHASE)
+}
+
+/// The midgame and endgame score of the pieces of `color`.
+fn evaluate_side(position: &Position, parameters: &Parameters, color: TeamColor) -> [i32; 2] {
+    let enemy = color.opposite();
+    let own = position.occupancy[color.index()];
+    let occupied = position.occupied();
+    let own_pawns = position.pieces_of(color, PieceVariant::Pawn);
+    let enemy_pawns = position.pieces_of(enemy, PieceVariant::Pawn);
+    let guarded_by_pawns = bitboard::squares(enemy_pawns).fold(0, |guarded, index| {
+        guarded | bitboard::pawn_attacks(enemy, index)
+    });
+    let enemy_king = position.king_square(enemy);
+    let king_zone = bitboard::king_attacks(enemy_king) | 1 << enemy_king;
+
+    let mut score = [0; 2];
+    for variant in VARIANTS {
+        let variant_index = variant.index();
+        for index in bitboard::squares(position.pieces_of(color, variant)) {
+            let square = table_square(color, index);
+            add(&mut score, |phase| {
+                parameters.material[phase][variant_index]
+                    + parameters.piece_squares[phase][variant_index][square]
+            });
+
+            let attacks = match variant {
+                PieceVariant::Queen => bitboard::queen_attacks(index, occupied),
+                PieceVariant::Castle => bitboard::rook_attacks(index, occupied),
+                PieceVariant::Bishop => bitboard::bishop_attacks(index, occupied),
+                PieceVariant::Knight => bitboard::knight_attacks(index),
+                PieceVariant::King | PieceVariant::Pawn => continue,
+            };
+            // mobility and king attacks have no entries for kings
+            let slot = variant_index - 1;
+            let mobility = (attacks & !own & !guarded_by_pawns).count_ones() as i32;
+            let king_attacks = (attacks & king_zone).count_ones() as i32;
+            add(&mut score, |phase| {
+                mobility * parameters.mobility[phase][slot]
+                    + king_attacks * parameters.king_attack[phase][slot]
+            });
+        }
+    }
+
+    for index in bitboard::squares(own_pawns) {
+        let (column, row) = bitboard::square_coordinates(index);
+        if own_pawns & neighbouring_files(column) == 0 {
+            add(&mut score, |phase| parameters.isolated_pawn[phase]);
+        }
+        let blockers =
+            enemy_pawns & (file(column) | neighbouring_files(column)) | own_pawns & file(column);
+        if blockers & ranks_ahead(color, row) == 0 {
+            let rank = relative_rank(color, row);
+            add(&mut score, |phase| parameters.passed_pawn[phase][rank]);
+        }
+    }
+    for column in 0..8 {
+        let doubled = (own_pawns & file(column)).count_ones().saturating_sub(1) as i32;
+        add(&mut score, |phase| doubled * parameters.doubled_pawn[phase]);
+    }
+
+    let (king_column, king_row) = bitboard::square_coordinates(position.king_square(color));
+    let shield = bitboard::squares(own_pawns & ranks_ahead(color, king_row))
+        .map(bitboard::square_coordinates)
+        .filter(|(column, row)| column.abs_diff(king_column) <= 1 && row.abs_diff(king_row) <= 2)
+        .count() as i32;
+    add(&mut score, |phase| shield * parameters.pawn_shield[phase]);
+
+    score
+}
+
+/// Scores the position in centipawns from the point of view of the side to
+/// move. Every term is weighed once for the midgame and once for the
+/// endgame, and the two are blended by how much material is left.
+pub fn evaluate(position: &Position, parameters: &Parameters) -> i32 {
+    let white = evaluate_side(position, parameters, TeamColor::White);
+    let black = evaluate_side(position, parameters, TeamColor::Black);
+    let phase = game_phase(position);
+    let score = ((white[MIDGAME] - black[MIDGAME]) * phase
+        + (white[ENDGAME] - black[ENDGAME]) * (OPENING_PHASE - phase))
+        / OPENING_PHASE;
+    match position.side_to_move {
+        TeamColor::White => score,
+        TeamColor::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+
+    fn evaluate_fen(position: &str) -> i32 {
+        evaluate(
+            &Position::from_fen(position).unwrap(),
+            &Parameters::default(),
+        )
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        assert_eq!(evaluate_fen(fen::STARTING_POSITION), 0);
+        assert_eq!(
+            evaluate_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"),
+            evaluate_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3")
+        );
+    }
+
+    #[test]
+    fn extra_material_is_good_for_its_owner() {
+        let up_a_queen = "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(evaluate_fen(up_a_queen) > 800);
+        assert!(evaluate_fen(&up_a_queen.replace(" w ", " b ")) < -800);
+    }
+
+    #[test]
+    fn pawn_structure_counts() {
+        // the same material, once with doubled and isolated pawns
+        let healthy = evaluate_fen("4k3/pp6/8/8/8/8/5PPP/4K3 w - - 0 1");
+        let broken = evaluate_fen("4k3/pp6/8/8/8/5P2/5P1P/4K3 w - - 0 1");
+        assert!(broken < healthy);
+
+        // a passed pawn is worth more the further it got
+        let far = evaluate_fen("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1");
+        let near = evaluate_fen("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1");
+        assert!(far > near + 50);
+    }
+
+    #[test]
+    fn weights_come_from_the_parameters() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap();
+        let mut parameters = Parameters::default();
+        let before = evaluate(&position, &parameters);
+        parameters.material[ENDGAME][PieceVariant::Knight.index()] += 100;
+        // with one knight left the position is almost all endgame
+        assert!(evaluate(&position, &parameters) > before + 90);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::game::{self, Board, Game, GameData, Piece, PieceVariant, TeamColor};
+use crate::produce;
+use crate::zobrist;
+use crate::Error;
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const STARTING_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// What is wrong with a FEN string. Ranks are numbered like on the board,
+/// so the first placement field reported is rank 8.
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    FieldCount(usize),
+    RankCount(usize),
+    RankLength {
+        rank: usize,
+        length: usize,
+    },
+    InvalidPiece {
+        rank: usize,
+        letter: char,
+    },
+    KingCount {
+        color: TeamColor,
+        count: usize,
+    },
+    /// A pawn on the first or last rank, where it cannot stand.
+    PawnOnBackRank(usize),
+    /// The side that just moved left its own king in check.
+    OpponentInCheck(TeamColor),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => {
+                write!(f, "expected 4 or 6 space separated fields, found {}", count)
+            }
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::RankLength { rank, length } => {
+                write!(f, "rank {} describes {} squares instead of 8", rank, length)
+            }
+            FenError::InvalidPiece { rank, letter } => {
+                write!(f, "rank {} contains unknown piece '{}'", rank, letter)
+            }
+            FenError::KingCount { color, count } => {
+                write!(f, "expected one {:?} king, found {}", color, count)
+            }
+            FenError::PawnOnBackRank(rank) => write!(f, "rank {} contains a pawn", rank),
+            FenError::OpponentInCheck(color) => {
+                write!(f, "{:?} is in check but it is not its turn", color)
+            }
+            FenError::InvalidSideToMove(field) => {
+                write!(f, "side to move must be 'w' or 'b', found '{}'", field)
+            }
+            FenError::InvalidCastling(field) => {
+                write!(
+                    f,
+                    "castling rights must be '-' or a subset of 'KQkq', found '{}'",
+                    field
+                )
+            }
+            FenError::InvalidEnPassant(field) => {
+                write!(f, "'{}' is not a valid en passant target square", field)
+            }
+            FenError::InvalidHalfmoveClock(field) => {
+                write!(f, "halfmove clock must be a number, found '{}'", field)
+            }
+            FenError::InvalidFullmoveNumber(field) => {
+                write!(
+                    f,
+                    "fullmove number must be a positive number, found '{}'",
+                    field
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn parse_placement(field: &str) -> Result<Board, FenError> {
+    let ranks: Vec<&str> = field.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    let mut board: Board = [[None; 8]; 8];
+    for (i, rank) in ranks.iter().enumerate() {
+        let row = 7 - i;
+        let mut column = 0;
+        for letter in rank.chars() {
+            if let Some(skip) = letter.to_digit(10).filter(|skip| (1..=8).contains(skip)) {
+                column += skip as usize;
+                continue;
+            }
+
+            let piece = Piece::from_char(letter).ok_or(FenError::InvalidPiece {
+                rank: row + 1,
+                letter,
+            })?;
+            if column < 8 {
+                board[row][column] = Some(piece);
+            }
+            column += 1;
+        }
+
+        if column != 8 {
+            return Err(FenError::RankLength {
+                rank: row + 1,
+                length: column,
+            });
+        }
+    }
+
+    for color in [TeamColor::White, TeamColor::Black] {
+        let count = board
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|piece| piece.variant == PieceVariant::King && piece.color == color)
+            .count();
+        if count != 1 {
+            return Err(FenError::KingCount { color, count });
+        }
+    }
+
+    for row in [0, 7] {
+        let has_pawn = board[row]
+            .iter()
+            .flatten()
+            .any(|piece| piece.variant == PieceVariant::Pawn);
+        if has_pawn {
+            return Err(FenError::PawnOnBackRank(row + 1));
+        }
+    }
+
+    Ok(board)
+}
+
+fn parse_castling(field: &str) -> Result<[[bool; 2]; 2], FenError> {
+    let mut is_valid_castling = [[false; 2]; 2];
+    if field == "-" {
+        return Ok(is_valid_castling);
+    }
+
+    for letter in field.chars() {
+        let (color, side) = match letter {
+            'K' => (TeamColor::White, 1),
+            'Q' => (TeamColor::White, 0),
+            'k' => (TeamColor::Black, 1),
+            'q' => (TeamColor::Black, 0),
+            _ => return Err(FenError::InvalidCastling(field.to_string())),
+        };
+
+        let right = &mut is_valid_castling[color.index()][side];
+        if *right {
+            return Err(FenError::InvalidCastling(field.to_string()));
+        }
+        *right = true;
+    }
+
+    Ok(is_valid_castling)
+}
+
+/// Turns the en passant target square into the square of the pawn that just
+/// advanced, which is what `GameData::recent_advancing_pawn` keeps.
+fn parse_en_passant(
+    field: &str,
+    board: &Board,
+    current_turn: TeamColor,
+) -> Result<Option<(usize, usize)>, FenError> {
+    if field == "-" {
+        return Ok(None);
+    }
+
+    let error = || FenError::InvalidEnPassant(field.to_string());
+    let target = game::parse_square(field).ok_or_else(error)?;
+    let (target_row, pawn_row) = match current_turn {
+        TeamColor::White => (5, 4),
+        TeamColor::Black => (2, 3),
+    };
+    let is_pawn_in_place = board[pawn_row][target.0]
+        .is_some_and(|piece| piece.variant == PieceVariant::Pawn && piece.color != current_turn);
+
+    if target.1 != target_row || !is_pawn_in_place {
+        return Err(error());
+    }
+    Ok(Some((target.0, pawn_row)))
+}
+
+/// Parses a FEN string into a board and the game data describing it, with
+/// the legal moves of the side to move already generated. The halfmove
+/// clock and fullmove number may be left out.
+pub fn parse(fen: &str) -> Result<(Board, GameData), FenError> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() != 4 && fields.len() != 6 {
+        return Err(FenError::FieldCount(fields.len()));
+    }
+
+    let board = parse_placement(fields[0])?;
+    let mut data = game::initialize_data();
+
+    data.current_turn = match fields[1] {
+        "w" => TeamColor::White,
+        "b" => TeamColor::Black,
+        field => return Err(FenError::InvalidSideToMove(field.to_string())),
+    };
+    data.is_valid_castling = parse_castling(fields[2])?;
+    data.recent_advancing_pawn = parse_en_passant(fields[3], &board, data.current_turn)?;
+
+    if fields.len() == 6 {
+        data.halfmove_clock = fields[4]
+            .parse()
+            .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+        data.fullmove_number = fields[5]
+            .parse()
+            .ok()
+            .filter(|number| *number > 0)
+            .ok_or_else(|| FenError::InvalidFullmoveNumber(fields[5].to_string()))?;
+    }
+
+    // the king of the side that just moved could be captured
+    let waiting = data.current_turn.opposite();
+    if produce::is_in_check(&board, &data, waiting) {
+        return Err(FenError::OpponentInCheck(waiting));
+    }
+
+    data.initial_position = serialize(&board, &data);
+    data.available_moves = produce::generate_legal_moves(&board, &data);
+    data.hash = zobrist::hash(&board, &data);
+    data.position_history.push(data.hash);
+
+    Ok((board, data))
+}
+
+/// Replaces the position of `game` with the one described by `fen`, keeping
+/// the side the local player picked.
+pub fn load(game: &mut Game, fen: &str) -> Result<(), FenError> {
+    let (board, mut data) = parse(fen)?;
+    data.player_color = game.cache.data.player_color;
+
+    game.board = board;
+    game.cache.data = data;
+    Ok(())
+}
+
+pub fn serialize(board: &Board, data: &GameData) -> String {
+    let mut placement = vec![];
+    for row in (0..8).rev() {
+        let mut rank = String::new();
+        let mut empty_squares = 0;
+        for square in board[row].iter() {
+            match square {
+                Some(piece) => {
+                    if empty_squares > 0 {
+                        rank.push_str(&empty_squares.to_string());
+                        empty_squares = 0;
+                    }
+                    rank.push(piece.to_char());
+                }
+                None => empty_squares += 1,
+            }
+        }
+        if empty_squares > 0 {
+            rank.push_str(&empty_squares.to_string());
+        }
+        placement.push(rank);
+    }
+
+    let side_to_move = match data.current_turn {
+        TeamColor::White => "w",
+        TeamColor::Black => "b",
+    };
+
+    let mut castling = String::new();
+    for (letter, color, side) in [
+        ('K', TeamColor::White, 1),
+        ('Q', TeamColor::White, 0),
+        ('k', TeamColor::Black, 1),
+        ('q', TeamColor::Black, 0),
+    ] {
+        if data.is_valid_castling[color.index()][side] {
+            castling.push(letter);
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    let en_passant = match data.recent_advancing_pawn {
+        Some((column, row)) => {
+            let target_row = if row == 3 { 2 } else { 5 };
+            game::square_name((column, target_row))
+        }
+        None => "-".to_string(),
+    };
+
+    format!(
+        "{} {} {} {} {} {}",
+        placement.join("/"),
+        side_to_move,
+        castling,
+        en_passant,
+        data.halfmove_clock,
+        data.fullmove_number
+    )
+}
+
+pub fn export(game: &Game) -> String {
+    serialize(&game.board, &game.cache.data)
+}
+
+/// Writes the current position to a new file in the working directory and
+/// returns its path.
+pub fn save(game: &Game) -> Result<String, Error> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("chess-{}.fen", timestamp);
+    std::fs::write(&path, export(game) + "\n")?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for fen in [
+            STARTING_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3",
+            "8/8/8/8/8/8/8/K6k b - - 57 120",
+        ] {
+            let (board, data) = parse(fen).unwrap();
+            assert_eq!(serialize(&board, &data), fen);
+            assert_eq!(data.initial_position, fen);
+        }
+    }
+
+    #[test]
+    fn counters_are_optional() {
+        let (board, data) = parse("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!(serialize(&board, &data), "8/8/8/8/8/8/8/K6k w - - 0 1");
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let errors = [
+            ("8/8/8/8/8/8/8/K6k w - - 0", FenError::FieldCount(5)),
+            ("8/8/8/8/8/8/K6k w - - 0 1", FenError::RankCount(7)),
+            (
+                "8/8/8/7/8/8/8/K6k w - - 0 1",
+                FenError::RankLength { rank: 5, length: 7 },
+            ),
+            (
+                "8/8/8/7x/8/8/8/K6k w - - 0 1",
+                FenError::InvalidPiece {
+                    rank: 5,
+                    letter: 'x',
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/K6K w - - 0 1",
+                FenError::KingCount {
+                    color: TeamColor::White,
+                    count: 2,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/6K1 w - - 0 1",
+                FenError::KingCount {
+                    color: TeamColor::Black,
+                    count: 0,
+                },
+            ),
+            ("k7/8/8/8/8/8/8/P6K w - - 0 1", FenError::PawnOnBackRank(1)),
+            ("k6p/8/8/8/8/8/8/7K w - - 0 1", FenError::PawnOnBackRank(8)),
+            (
+                "k7/8/8/8/8/8/1p6/R6K w - - 0 1",
+                FenError::OpponentInCheck(TeamColor::Black),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k x - - 0 1",
+                FenError::InvalidSideToMove("x".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k w KK - 0 1",
+                FenError::InvalidCastling("KK".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k w X - 0 1",
+                FenError::InvalidCastling("X".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k w - e6 0 1",
+                FenError::InvalidEnPassant("e6".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k w - - x 1",
+                FenError::InvalidHalfmoveClock("x".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/K6k w - - 0 0",
+                FenError::InvalidFullmoveNumber("0".to_string()),
+            ),
+        ];
+        for (fen, error) in errors {
+            assert_eq!(parse(fen).err(), Some(error), "{}", fen);
+        }
+    }
+}
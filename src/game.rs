@@ -1,22 +1,76 @@
+use crate::computer::Computer;
+use crate::fen;
+use crate::parameters::Parameters;
+use crate::pgn::Replay;
+use crate::polyglot::Book;
 use crate::produce::{self, Move};
+use crate::syzygy::{Tablebases, Verdict};
+use crate::transposition;
+use crate::zobrist;
 use crate::Error;
 
 use sdl2::render::{TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
 
 use std::collections::HashSet;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 pub struct Game {
     pub state: GameState,
     pub texture_creator: TextureCreator<WindowContext>,
-    pub board: [[Option<Piece>; 8]; 8],
+    pub board: Board,
     pub cache: Cache,
+    /// FEN of the position new games start from instead of the usual one.
+    pub starting_position: Option<String>,
+    pub replay: Option<Replay>,
+    /// Positions before each move played, most recent last.
+    pub undo_stack: Vec<(Board, GameData)>,
+    /// Positions taken back with undo, most recent last.
+    pub redo_stack: Vec<(Board, GameData)>,
+    /// The computer opponent, when not playing hot-seat.
+    pub computer: Option<Computer>,
+    /// Memory the computer's transposition table is given.
+    pub hash_megabytes: usize,
+    /// The evaluation weights the computer plays with.
+    pub parameters: Arc<Parameters>,
+    /// The opening book the computer plays its first moves from.
+    pub book: Option<Arc<Book>>,
+    /// Endgame tablebases for the computer and the analysis view.
+    pub tablebases: Option<Arc<Tablebases>>,
 }
 
 impl Game {
     pub fn get_square(&self, column: usize, row: usize) -> Option<&Piece> {
         self.board.get(row)?.get(column)?.as_ref()
     }
+
+    /// Whether the game is on and waiting for the computer to move.
+    pub fn is_computer_turn(&self) -> bool {
+        let is_playing = matches!(self.state, GameState::BoardGame);
+        self.computer
+            .as_ref()
+            .is_some_and(|computer| is_playing && computer.color == self.cache.data.current_turn)
+    }
+}
+
+/// Squares indexed as `board[row][column]`, where row 0 is the first rank
+/// and column 0 the a-file, so White always moves up the rows no matter
+/// which side the local player picked.
+pub type Board = [[Option<Piece>; 8]; 8];
+
+/// The algebraic name of a square, e.g. `(4, 3)` is "e4".
+pub fn square_name(square: (usize, usize)) -> String {
+    format!("{}{}", (b'a' + square.0 as u8) as char, square.1 + 1)
+}
+
+pub fn parse_square(name: &str) -> Option<(usize, usize)> {
+    match name.as_bytes() {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+            Some(((file - b'a') as usize, (rank - b'1') as usize))
+        }
+        _ => None,
+    }
 }
 
 pub struct Cache {
@@ -25,17 +79,69 @@ pub struct Cache {
     pub board_offset: (f32, f32),
     pub square_size: (f32, f32),
     pub data: GameData,
+    /// The analysis view, toggled with ctrl+A.
+    pub analysis: Option<Analysis>,
+}
+
+/// The tablebase verdict shown in the analysis view, for the position with
+/// `hash`.
+pub struct Analysis {
+    pub hash: u64,
+    pub verdict: Option<Verdict>,
+    /// The probe running on its own thread, until it has answered.
+    pub probe: Option<Receiver<Option<Verdict>>>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct GameData {
     pub focused_square: Option<(usize, usize)>,
     pub recent_advancing_pawn: Option<(usize, usize)>,
     pub recent_promoting_pawn: Option<(usize, usize)>,
     pub current_turn: TeamColor,
+    /// The side shown at the bottom of the screen.
     pub player_color: TeamColor,
     pub available_moves: HashSet<Move>,
     pub danger_squares: Vec<(usize, usize)>,
+    /// Castling rights indexed by `TeamColor::index` and then by the rook
+    /// side: 0 for the a-file rook, 1 for the h-file rook.
     pub is_valid_castling: [[bool; 2]; 2],
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    /// Zobrist key of the position, kept up to date by `produce::make_move`.
+    pub hash: u64,
+    /// Zobrist keys of every position reached so far, for the repetition rules.
+    pub position_history: Vec<u64>,
+    /// FEN of the position the game started from.
+    pub initial_position: String,
+    /// The moves played so far in Standard Algebraic Notation.
+    pub move_list: Vec<String>,
+    /// A move being typed in on the keyboard, resolved as SAN on enter.
+    pub typed_move: String,
+}
+
+impl GameData {
+    /// Counts how often the latest recorded position has occurred so far.
+    pub fn repetition_count(&self) -> usize {
+        match self.position_history.last() {
+            Some(last) => self
+                .position_history
+                .iter()
+                .filter(|hash| *hash == last)
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Returns the draw the side to move may claim, if any.
+    pub fn claimable_draw(&self) -> Option<GameOverReason> {
+        if self.repetition_count() >= 3 {
+            Some(GameOverReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(GameOverReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -44,6 +150,42 @@ pub struct Piece {
     pub color: TeamColor,
 }
 
+impl Piece {
+    /// The FEN letter of the piece, uppercase for White.
+    pub fn to_char(self) -> char {
+        let letter = match self.variant {
+            PieceVariant::King => 'k',
+            PieceVariant::Queen => 'q',
+            PieceVariant::Castle => 'r',
+            PieceVariant::Bishop => 'b',
+            PieceVariant::Knight => 'n',
+            PieceVariant::Pawn => 'p',
+        };
+        match self.color {
+            TeamColor::White => letter.to_ascii_uppercase(),
+            TeamColor::Black => letter,
+        }
+    }
+
+    pub fn from_char(letter: char) -> Option<Piece> {
+        let variant = match letter.to_ascii_lowercase() {
+            'k' => PieceVariant::King,
+            'q' => PieceVariant::Queen,
+            'r' => PieceVariant::Castle,
+            'b' => PieceVariant::Bishop,
+            'n' => PieceVariant::Knight,
+            'p' => PieceVariant::Pawn,
+            _ => return None,
+        };
+        let color = if letter.is_ascii_uppercase() {
+            TeamColor::White
+        } else {
+            TeamColor::Black
+        };
+        Some(Piece { variant, color })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum PieceVariant {
     King,
@@ -54,17 +196,87 @@ pub enum PieceVariant {
     Pawn,
 }
 
+impl PieceVariant {
+    /// Index of the piece kind in per-kind tables, in declaration order.
+    pub fn index(self) -> usize {
+        match self {
+            PieceVariant::King => 0,
+            PieceVariant::Queen => 1,
+            PieceVariant::Castle => 2,
+            PieceVariant::Bishop => 3,
+            PieceVariant::Knight => 4,
+            PieceVariant::Pawn => 5,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TeamColor {
     White,
     Black,
 }
 
+impl TeamColor {
+    pub fn opposite(self) -> TeamColor {
+        match self {
+            TeamColor::White => TeamColor::Black,
+            TeamColor::Black => TeamColor::White,
+        }
+    }
+
+    /// Index of the color in per-color tables such as `is_valid_castling`.
+    pub fn index(self) -> usize {
+        match self {
+            TeamColor::White => 0,
+            TeamColor::Black => 1,
+        }
+    }
+
+    /// The row the king and rooks of the color start on.
+    pub fn home_row(self) -> usize {
+        match self {
+            TeamColor::White => 0,
+            TeamColor::Black => 7,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameOverReason {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl GameResult {
+    pub fn win_for(color: TeamColor) -> GameResult {
+        match color {
+            TeamColor::White => GameResult::WhiteWins,
+            TeamColor::Black => GameResult::BlackWins,
+        }
+    }
+}
+
 pub enum GameState {
     StartMenu,
     TeamSelection,
+    DifficultySelection,
     BoardGame,
     PromoteSelection,
+    GameOver(GameResult, GameOverReason),
+    Replay,
 }
 
 pub fn initialize_game(canvas: &WindowCanvas) -> Result<Game, Error> {
@@ -73,6 +285,15 @@ pub fn initialize_game(canvas: &WindowCanvas) -> Result<Game, Error> {
         board: [[None; 8]; 8],
         texture_creator: canvas.texture_creator(),
         cache: initialize_cache(canvas)?,
+        starting_position: None,
+        replay: None,
+        undo_stack: vec![],
+        redo_stack: vec![],
+        computer: None,
+        hash_megabytes: transposition::DEFAULT_MEGABYTES,
+        parameters: Arc::new(Parameters::default()),
+        book: None,
+        tablebases: None,
     })
 }
 
@@ -93,6 +314,7 @@ fn initialize_cache(canvas: &WindowCanvas) -> Result<Cache, Error> {
         board_offset,
         square_size,
         data: initialize_data(),
+        analysis: None,
     })
 }
 
@@ -106,17 +328,20 @@ pub fn initialize_data() -> GameData {
         available_moves: HashSet::new(),
         danger_squares: vec![],
         is_valid_castling: [[true; 2]; 2],
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: 0,
+        position_history: vec![],
+        initial_position: fen::STARTING_POSITION.to_string(),
+        move_list: vec![],
+        typed_move: String::new(),
     }
 }
 
-pub fn init_chess_position(game: &mut Game, color: TeamColor) {
+pub fn init_chess_position(game: &mut Game) {
     use PieceVariant::*;
 
     let mut board = [[None; 8]; 8];
-    let (king_queen_order, initial_rows) = match color {
-        TeamColor::Black => ([King, Queen], [6, 1, 7, 0]),
-        TeamColor::White => ([Queen, King], [1, 6, 0, 7]),
-    };
     let init_piece_on_column = |x: usize, color: TeamColor| match x {
         0 | 7 => Some(Piece {
             variant: Castle,
@@ -131,29 +356,32 @@ pub fn init_chess_position(game: &mut Game, color: TeamColor) {
             color,
         }),
         3 => Some(Piece {
-            variant: king_queen_order[0],
+            variant: Queen,
             color,
         }),
         4 => Some(Piece {
-            variant: king_queen_order[1],
+            variant: King,
             color,
         }),
         _ => unreachable!("unknown column"),
     };
 
     for column in 0..8 {
-        board[initial_rows[0]][column] = Some(Piece {
+        board[1][column] = Some(Piece {
             variant: Pawn,
-            color: TeamColor::Black,
+            color: TeamColor::White,
         });
-        board[initial_rows[1]][column] = Some(Piece {
+        board[6][column] = Some(Piece {
             variant: Pawn,
-            color: TeamColor::White,
+            color: TeamColor::Black,
         });
-        board[initial_rows[2]][column] = init_piece_on_column(column, TeamColor::Black);
-        board[initial_rows[3]][column] = init_piece_on_column(column, TeamColor::White);
+        board[0][column] = init_piece_on_column(column, TeamColor::White);
+        board[7][column] = init_piece_on_column(column, TeamColor::Black);
     }
 
     game.board = board;
     game.cache.data.available_moves = produce::generate_moves(game);
+
+    game.cache.data.hash = zobrist::hash(&game.board, &game.cache.data);
+    game.cache.data.position_history.push(game.cache.data.hash);
 }
@@ -1,9 +1,11 @@
-use crate::game::{Game, Piece, PieceVariant};
+use crate::game::{Game, Piece, PieceVariant, TeamColor};
 use crate::produce::{Move, MoveType};
+use crate::san;
+use crate::uci;
 use crate::Command;
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::mouse::MouseButton;
 use sdl2::rect::Rect;
 
@@ -23,17 +25,22 @@ fn is_piece_ally(game: &Game, piece: &Piece) -> bool {
 }
 
 fn into_relative_position(game: &Game, pos: (i32, i32)) -> (usize, usize) {
-    (
-        ((pos.0 as f32 - game.cache.board_offset.0) / game.cache.square_size.0 as f32) as usize,
-        ((pos.1 as f32 - game.cache.board_offset.1) / game.cache.square_size.1 as f32) as usize,
-    )
+    let column =
+        ((pos.0 as f32 - game.cache.board_offset.0) / game.cache.square_size.0 as f32) as usize;
+    let row =
+        ((pos.1 as f32 - game.cache.board_offset.1) / game.cache.square_size.1 as f32) as usize;
+
+    // the board is stored with White at row 0, flip it to the player's side
+    match game.cache.data.player_color {
+        TeamColor::White => (column, 7 - row),
+        TeamColor::Black => (7 - column, row),
+    }
 }
 
 fn is_move_promotion(game: &Game, row: usize) -> bool {
     let focused_square = game.cache.data.focused_square.unwrap_or((8, 8));
     let from = game.get_square(focused_square.0, focused_square.1);
-    let is_ally = game.cache.data.current_turn == game.cache.data.player_color;
-    let end_row = if is_ally { 0 } else { 7 };
+    let end_row = game.cache.data.current_turn.opposite().home_row();
     match from {
         Some(piece) if row == end_row => {
             is_piece_ally(game, piece) && piece.variant == PieceVariant::Pawn
@@ -45,9 +52,9 @@ fn is_move_promotion(game: &Game, row: usize) -> bool {
 fn is_move_advancing_pawn(game: &Game, row: usize) -> bool {
     let focused_square = game.cache.data.focused_square.unwrap_or((8, 8));
     let from = game.get_square(focused_square.0, focused_square.1);
-    let is_ally = game.cache.data.current_turn == game.cache.data.player_color;
-    let start_row = if is_ally { 6 } else { 1 };
-    let end_row = if is_ally { 4 } else { 3 };
+    let is_white = game.cache.data.current_turn == TeamColor::White;
+    let start_row = if is_white { 1 } else { 6 };
+    let end_row = if is_white { 3 } else { 4 };
     match from {
         Some(piece) if row == end_row && focused_square.1 == start_row => {
             is_piece_ally(game, piece) && piece.variant == PieceVariant::Pawn
@@ -81,6 +88,9 @@ fn is_move_castling(game: &Game, column: usize) -> bool {
 }
 
 fn handle_mouse_on_board(game: &Game, pos: (i32, i32)) -> Vec<Command> {
+    if game.is_computer_turn() {
+        return vec![Command::Unfocus];
+    }
     let (column, row) = into_relative_position(game, pos);
     let has_focused_square = game.cache.data.focused_square.is_some();
     let is_valid_move = game.cache.data.danger_squares.contains(&(column, row));
@@ -140,17 +150,69 @@ fn handle_mouse_on_board(game: &Game, pos: (i32, i32)) -> Vec<Command> {
     }
 }
 
+fn is_cursor_inside_undo_rect(game: &Game, pos: (i32, i32)) -> bool {
+    let undo_rect = Rect::new(
+        5,
+        (game.cache.board_offset.1 + game.cache.board_size.1 / 2.0 - 20.0) as i32,
+        (game.cache.board_offset.0 - 10.0) as u32,
+        40,
+    );
+
+    undo_rect.contains_point(pos)
+}
+
+fn is_cursor_inside_redo_rect(game: &Game, pos: (i32, i32)) -> bool {
+    let redo_rect = Rect::new(
+        (game.cache.board_offset.0 + game.cache.board_size.0 + 5.0) as i32,
+        (game.cache.board_offset.1 + game.cache.board_size.1 / 2.0 - 20.0) as i32,
+        (game.cache.board_offset.0 - 10.0) as u32,
+        40,
+    );
+
+    redo_rect.contains_point(pos)
+}
+
 fn handle_mousedown(game: &Game, mouse_btn: MouseButton, pos: (i32, i32)) -> Vec<Command> {
     match mouse_btn {
         MouseButton::Left if is_cursor_inside_board(game, pos) => handle_mouse_on_board(game, pos),
+        MouseButton::Left if is_cursor_inside_undo_rect(game, pos) => vec![Command::Undo],
+        MouseButton::Left if is_cursor_inside_redo_rect(game, pos) => vec![Command::Redo],
         MouseButton::Left => vec![Command::Unfocus],
         _ => vec![],
     }
 }
 
-fn handle_keydown(keycode: Option<Keycode>) -> Vec<Command> {
+fn handle_typed_move(game: &Game) -> Vec<Command> {
+    if game.is_computer_turn() {
+        return vec![Command::ClearTypedMove];
+    }
+    let typed_move = &game.cache.data.typed_move;
+    let move_data = san::parse(&game.board, &game.cache.data, typed_move)
+        .or_else(|_| uci::parse(&game.cache.data, typed_move));
+    match move_data {
+        Ok(move_data) => vec![
+            Command::ClearTypedMove,
+            Command::Unfocus,
+            Command::Move(move_data),
+            Command::ChangeTurn,
+        ],
+        Err(_) => vec![Command::ClearTypedMove],
+    }
+}
+
+fn handle_keydown(game: &Game, keycode: Option<Keycode>, keymod: Mod) -> Vec<Command> {
+    let is_ctrl_pressed = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
     match keycode {
         Some(Keycode::Escape) => vec![Command::ExitGame],
+        Some(Keycode::Return) => handle_typed_move(game),
+        Some(Keycode::Backspace) => vec![Command::EraseTypedMove],
+        Some(Keycode::P) if is_ctrl_pressed => vec![Command::ExportPgn],
+        Some(Keycode::R) if is_ctrl_pressed => vec![Command::Unfocus, Command::Resign],
+        Some(Keycode::D) if is_ctrl_pressed => vec![Command::Unfocus, Command::ClaimDraw],
+        Some(Keycode::F) if is_ctrl_pressed => vec![Command::ExportFen],
+        Some(Keycode::A) if is_ctrl_pressed => vec![Command::ToggleAnalysis],
+        Some(Keycode::Z) if is_ctrl_pressed => vec![Command::Undo],
+        Some(Keycode::Y) if is_ctrl_pressed => vec![Command::Redo],
         _ => vec![Command::Idle],
     }
 }
@@ -158,7 +220,10 @@ fn handle_keydown(keycode: Option<Keycode>) -> Vec<Command> {
 pub fn handle_event(event: Event, game: &Game) -> Vec<Command> {
     match event {
         Event::Quit { .. } => vec![Command::Quit],
-        Event::KeyDown { keycode, .. } => handle_keydown(keycode),
+        Event::KeyDown {
+            keycode, keymod, ..
+        } => handle_keydown(game, keycode, keymod),
+        Event::TextInput { text, .. } => vec![Command::TypeMove(text)],
         Event::MouseButtonDown {
             mouse_btn, x, y, ..
         } => handle_mousedown(game, mouse_btn, (x, y)),
@@ -0,0 +1,61 @@
+use crate::computer::Difficulty;
+use crate::game::Game;
+use crate::Command;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::rect::Rect;
+
+fn difficulty_under_cursor(game: &Game, pos: (i32, i32)) -> Option<Difficulty> {
+    let window_size = game.cache.window_size;
+    let button_width = window_size.0 * 0.40;
+    let button_height = window_size.1 * 0.10;
+
+    Difficulty::ALL
+        .into_iter()
+        .enumerate()
+        .find_map(|(i, difficulty)| {
+            let button_rect = Rect::new(
+                ((window_size.0 - button_width) / 2.0) as i32,
+                (window_size.1 * 0.22 + i as f32 * button_height * 1.4) as i32,
+                button_width as u32,
+                button_height as u32,
+            );
+            button_rect.contains_point(pos).then_some(difficulty)
+        })
+}
+
+fn handle_mousedown(game: &Game, mouse_btn: MouseButton, pos: (i32, i32)) -> Vec<Command> {
+    match mouse_btn {
+        MouseButton::Left => match difficulty_under_cursor(game, pos) {
+            Some(difficulty) => vec![Command::SelectDifficulty(difficulty)],
+            None => vec![Command::Idle],
+        },
+        _ => vec![Command::Idle],
+    }
+}
+
+fn handle_keydown(keycode: Option<Keycode>) -> Vec<Command> {
+    let level = match keycode {
+        Some(Keycode::Escape) => return vec![Command::ExitGame],
+        Some(Keycode::Num1) => 0,
+        Some(Keycode::Num2) => 1,
+        Some(Keycode::Num3) => 2,
+        Some(Keycode::Num4) => 3,
+        Some(Keycode::Num5) => 4,
+        _ => return vec![Command::Idle],
+    };
+    vec![Command::SelectDifficulty(Difficulty::ALL[level])]
+}
+
+pub fn handle_event(event: Event, game: &Game) -> Vec<Command> {
+    match event {
+        Event::Quit { .. } => vec![Command::Quit],
+        Event::KeyDown { keycode, .. } => handle_keydown(keycode),
+        Event::MouseButtonDown {
+            mouse_btn, x, y, ..
+        } => handle_mousedown(game, mouse_btn, (x, y)),
+        _ => vec![Command::Idle],
+    }
+}
@@ -0,0 +1,39 @@
+use crate::display::game_over::get_button_rects;
+use crate::game::Game;
+use crate::Command;
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+
+fn handle_mousedown(game: &Game, mouse_btn: MouseButton, pos: (i32, i32)) -> Vec<Command> {
+    let (rematch_rect, menu_rect) = get_button_rects(game);
+    match mouse_btn {
+        MouseButton::Left if rematch_rect.contains_point(pos) => vec![Command::Rematch],
+        MouseButton::Left if menu_rect.contains_point(pos) => vec![Command::ExitGame],
+        _ => vec![Command::Idle],
+    }
+}
+
+fn handle_keydown(keycode: Option<Keycode>, keymod: Mod) -> Vec<Command> {
+    let is_ctrl_pressed = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    match keycode {
+        Some(Keycode::Z) if is_ctrl_pressed => vec![Command::Undo],
+        Some(Keycode::Escape) => vec![Command::ExitGame],
+        Some(Keycode::P) if is_ctrl_pressed => vec![Command::ExportPgn],
+        _ => vec![Command::Idle],
+    }
+}
+
+pub fn handle_event(event: Event, game: &Game) -> Vec<Command> {
+    match event {
+        Event::Quit { .. } => vec![Command::Quit],
+        Event::KeyDown {
+            keycode, keymod, ..
+        } => handle_keydown(keycode, keymod),
+        Event::MouseButtonDown {
+            mouse_btn, x, y, ..
+        } => handle_mousedown(game, mouse_btn, (x, y)),
+        _ => vec![Command::Idle],
+    }
+}
@@ -1,5 +1,8 @@
 mod board_game;
+mod difficulty_selection;
+mod game_over;
 mod promote_selection;
+mod replay;
 mod start_menu;
 mod team_selection;
 
@@ -12,7 +15,10 @@ pub fn handle_event(event: Event, game: &Game) -> Vec<Command> {
     match game.state {
         GameState::StartMenu => start_menu::handle_event(event, game),
         GameState::TeamSelection => team_selection::handle_event(event, game),
+        GameState::DifficultySelection => difficulty_selection::handle_event(event, game),
         GameState::BoardGame => board_game::handle_event(event, game),
         GameState::PromoteSelection => promote_selection::handle_event(event, game),
+        GameState::GameOver(..) => game_over::handle_event(event, game),
+        GameState::Replay => replay::handle_event(event),
     }
 }
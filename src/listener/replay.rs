@@ -0,0 +1,23 @@
+use crate::Command;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+fn handle_keydown(keycode: Option<Keycode>) -> Vec<Command> {
+    match keycode {
+        Some(Keycode::Escape) => vec![Command::ExitGame],
+        Some(Keycode::Right) => vec![Command::StepForward],
+        Some(Keycode::Left) => vec![Command::StepBackward],
+        Some(Keycode::Down) => vec![Command::NextGame],
+        Some(Keycode::Up) => vec![Command::PreviousGame],
+        _ => vec![Command::Idle],
+    }
+}
+
+pub fn handle_event(event: Event) -> Vec<Command> {
+    match event {
+        Event::Quit { .. } => vec![Command::Quit],
+        Event::KeyDown { keycode, .. } => handle_keydown(keycode),
+        _ => vec![Command::Idle],
+    }
+}
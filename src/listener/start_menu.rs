@@ -13,7 +13,7 @@ fn is_cursor_inside_quit_rect(game: &Game, pos: (i32, i32)) -> bool {
 
     let quit_rect = Rect::new(
         ((window_size.0 - button_width) / 2.0) as i32,
-        ((window_size.1 - button_height) / 2.0 + button_height * 1.2) as i32,
+        ((window_size.1 - button_height) / 2.0 + button_height * 2.4) as i32,
         button_width as u32,
         button_height as u32,
     );
@@ -21,6 +21,21 @@ fn is_cursor_inside_quit_rect(game: &Game, pos: (i32, i32)) -> bool {
     quit_rect.contains_point(pos)
 }
 
+fn is_cursor_inside_computer_rect(game: &Game, pos: (i32, i32)) -> bool {
+    let window_size = game.cache.window_size;
+    let button_width = window_size.0 * 0.30;
+    let button_height = window_size.1 * 0.12;
+
+    let computer_rect = Rect::new(
+        ((window_size.0 - button_width) / 2.0) as i32,
+        ((window_size.1 - button_height) / 2.0 + button_height * 1.2) as i32,
+        button_width as u32,
+        button_height as u32,
+    );
+
+    computer_rect.contains_point(pos)
+}
+
 fn is_cursor_inside_play_rect(game: &Game, pos: (i32, i32)) -> bool {
     let window_size = game.cache.window_size;
     let button_width = window_size.0 * 0.30;
@@ -40,6 +55,9 @@ fn handle_mousedown(game: &Game, mouse_btn: MouseButton, pos: (i32, i32)) -> Vec
     match mouse_btn {
         MouseButton::Left if is_cursor_inside_quit_rect(game, pos) => vec![Command::Quit],
         MouseButton::Left if is_cursor_inside_play_rect(game, pos) => vec![Command::Play],
+        MouseButton::Left if is_cursor_inside_computer_rect(game, pos) => {
+            vec![Command::PlayComputer]
+        }
         _ => vec![Command::Idle],
     }
 }
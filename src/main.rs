@@ -1,14 +1,32 @@
+pub mod bitboard;
+pub mod clock;
 pub mod config;
+pub mod evaluate;
+pub mod fen;
 pub mod game;
+pub mod ordering;
+pub mod parameters;
+pub mod perft;
+pub mod pgn;
+pub mod polyglot;
 pub mod produce;
+pub mod san;
+pub mod search;
+pub mod syzygy;
+pub mod transposition;
+pub mod tune;
+pub mod uci;
+pub mod zobrist;
 
 mod amend;
+mod computer;
 mod display;
 mod listener;
 
 use sdl2::image::LoadTexture;
 use sdl2::render::Texture;
 
+use computer::Difficulty;
 use game::TeamColor;
 use produce::Move;
 
@@ -25,13 +43,169 @@ pub enum Command {
     Unfocus,
     Focus(usize, usize),
     SelectTeam(TeamColor),
+    SelectDifficulty(Difficulty),
+    Rematch,
+    Resign,
+    ClaimDraw,
+    ExportFen,
+    ExportPgn,
+    ToggleAnalysis,
+    Undo,
+    Redo,
+    TypeMove(String),
+    EraseTypedMove,
+    ClearTypedMove,
+    ShowReplay,
+    StepForward,
+    StepBackward,
+    NextGame,
+    PreviousGame,
     ExitGame,
     Play,
+    PlayComputer,
     Quit,
     Idle,
 }
 
-fn main() -> Result<(), Error> {
+/// Reads the value following `name` on the command line, if it was given.
+fn read_argument(name: &str) -> Result<Option<String>, Error> {
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            let value = args.next().ok_or(format!("missing value after {}", name))?;
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+/// Prints the perft count of every root move and their total.
+fn print_perft(position: &str, depth: &str) -> Result<(), Error> {
+    let position = bitboard::Position::from_fen(position)?;
+    let counts = perft::divide_position(&position, depth.parse()?);
+    for (move_name, nodes) in counts.iter() {
+        println!("{}: {}", move_name, nodes);
+    }
+    println!();
+    println!(
+        "Nodes searched: {}",
+        counts.iter().map(|(_, nodes)| nodes).sum::<u64>()
+    );
+    Ok(())
+}
+
+/// Tunes `parameters` on the labeled positions at `path`, writing the
+/// weights to `output` after every pass so an interrupted run keeps them.
+fn tune_evaluation(
+    path: &str,
+    mut parameters: parameters::Parameters,
+    output: &str,
+) -> Result<(), Error> {
+    let positions = tune::parse_positions(&std::fs::read_to_string(path)?)?;
+    let scaling = tune::fit_scaling(&positions, &parameters);
+    println!(
+        "{} positions, scaling {:.3}, error {:.6}",
+        positions.len(),
+        scaling,
+        tune::mean_squared_error(&positions, &parameters, scaling)
+    );
+
+    let mut write_error = None;
+    tune::tune(
+        &positions,
+        &mut parameters,
+        scaling,
+        usize::MAX,
+        |pass, error, parameters| {
+            println!("pass {}: error {:.6}", pass, error);
+            if let Err(error) = std::fs::write(output, parameters::render(parameters)) {
+                write_error.get_or_insert(error);
+            }
+        },
+    );
+    match write_error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+/// Writes a Polyglot book of the first `depth` plies of the games at `path`.
+fn build_book(path: &str, output: &str, depth: usize) -> Result<(), Error> {
+    let games = pgn::parse_games(&std::fs::read_to_string(path)?)?;
+    let mut builder = polyglot::BookBuilder::new(depth);
+    let used = games
+        .iter()
+        .filter(|pgn_game| builder.add_game(pgn_game))
+        .count();
+    let entries = builder.entries();
+    std::fs::write(output, polyglot::to_bytes(&entries))?;
+    println!(
+        "{} of {} games used, {} book entries written to {}",
+        used,
+        games.len(),
+        entries.len(),
+        output
+    );
+    Ok(())
+}
+
+/// Runs the command line tool or the game, depending on the arguments.
+fn run() -> Result<(), Error> {
+    let starting_position = read_argument("--fen")?;
+    if let Some(ref position) = starting_position {
+        fen::parse(position)?;
+    }
+    let parameters = match read_argument("--evaluation")? {
+        Some(path) => parameters::load(&path)?,
+        None => parameters::Parameters::default(),
+    };
+    if let Some(depth) = read_argument("--perft")? {
+        let position = starting_position
+            .as_deref()
+            .unwrap_or(fen::STARTING_POSITION);
+        return print_perft(position, &depth);
+    }
+    if let Some(path) = read_argument("--build-book")? {
+        let output = read_argument("--output")?.unwrap_or_else(|| "book.bin".to_string());
+        let depth = match read_argument("--book-depth")? {
+            Some(depth) => depth.parse()?,
+            None => 20,
+        };
+        return build_book(&path, &output, depth);
+    }
+    if let Some(path) = read_argument("--tune")? {
+        let output = read_argument("--output")?.unwrap_or_else(|| "evaluation.txt".to_string());
+        return tune_evaluation(&path, parameters, &output);
+    }
+    let replay = match read_argument("--pgn")? {
+        Some(path) => Some(pgn::load_replay(&path)?),
+        None => None,
+    };
+    // without a book on the command line the default one may be missing,
+    // but not broken
+    let book = match read_argument("--book")? {
+        Some(path) => Some(polyglot::Book::load(&path)?),
+        None => match polyglot::Book::load(polyglot::DEFAULT_BOOK_PATH) {
+            Ok(book) => Some(book),
+            Err(error)
+                if error
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|error| error.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                None
+            }
+            Err(error) => return Err(error),
+        },
+    };
+    let tablebases = match read_argument("--syzygy")? {
+        Some(directories) => Some(syzygy::Tablebases::open(&directories)?),
+        None => None,
+    };
+    let hash_megabytes = match read_argument("--hash")? {
+        Some(megabytes) => megabytes.parse()?,
+        None => transposition::DEFAULT_MEGABYTES,
+    };
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let ttf_context = sdl2::ttf::init()?;
@@ -45,6 +219,15 @@ fn main() -> Result<(), Error> {
 
     let mut canvas = window.into_canvas().build()?;
     let mut game = game::initialize_game(&canvas)?;
+    game.starting_position = starting_position;
+    game.parameters = std::sync::Arc::new(parameters);
+    game.book = book.map(std::sync::Arc::new);
+    game.tablebases = tablebases.map(std::sync::Arc::new);
+    game.hash_megabytes = hash_megabytes;
+    if replay.is_some() {
+        game.replay = replay;
+        amend::update(vec![Command::ShowReplay], &mut game);
+    }
     let texture_creator = canvas.texture_creator();
     let textures = Textures {
         pieces: texture_creator.load_texture("assets/chess_pieces.png")?,
@@ -59,6 +242,8 @@ fn main() -> Result<(), Error> {
         for event in event_pump.poll_iter() {
             amend::update(listener::handle_event(event, &game), &mut game);
         }
+        let commands = computer::handle_turn(&mut game);
+        amend::update(commands, &mut game);
 
         display::render(&mut canvas, &configuration, &game, &textures)?;
 
@@ -66,3 +251,10 @@ fn main() -> Result<(), Error> {
         ::std::thread::sleep(::std::time::Duration::new(0, 1_000_000_000u32 / 40));
     }
 }
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
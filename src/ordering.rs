@@ -0,0 +1,281 @@
+use crate::bitboard::{BitMove, Bitboard, Position};
+use crate::evaluate::PIECE_VALUES;
+use crate::game::{PieceVariant, TeamColor};
+use crate::produce::MoveType;
+
+/// Deeper than any line the search follows, quiescence included.
+pub const MAX_PLY: usize = 128;
+
+const TABLE_MOVE_SCORE: i32 = 1 << 24;
+const GOOD_CAPTURE_SCORE: i32 = 1 << 22;
+const KILLER_SCORES: [i32; 2] = [1 << 21, (1 << 21) - 1];
+const BAD_CAPTURE_SCORE: i32 = -(1 << 22);
+/// History scores are kept below the killers.
+const HISTORY_LIMIT: i32 = 1 << 20;
+
+/// The value of a king in the exchange evaluation, high enough that no
+/// exchange is worth giving it up.
+const KING_VALUE: i32 = 20_000;
+
+fn piece_value(variant: PieceVariant) -> i32 {
+    match variant {
+        PieceVariant::King => KING_VALUE,
+        _ => PIECE_VALUES[variant.index()],
+    }
+}
+
+fn is_capture(move_data: BitMove) -> bool {
+    matches!(move_data.variant, MoveType::Capture | MoveType::EnPassant)
+}
+
+/// The value of what `move_data` takes, a pawn for en passant.
+fn captured_value(position: &Position, move_data: BitMove) -> i32 {
+    match move_data.variant {
+        MoveType::EnPassant => PIECE_VALUES[PieceVariant::Pawn.index()],
+        _ => position
+            .piece_at(move_data.to as usize)
+            .map_or(0, |piece| piece_value(piece.variant)),
+    }
+}
+
+/// Most valuable victim, least valuable attacker: captures of big pieces
+/// first, and among those, the ones made with the smallest piece.
+pub fn mvv_lva(position: &Position, move_data: BitMove) -> i32 {
+    let attacker = position
+        .piece_at(move_data.from as usize)
+        .map_or(0, |piece| piece.variant.index() as i32);
+    // the variant index grows as pieces get smaller, kings aside
+    captured_value(position, move_data) * 8 + attacker
+}
+
+/// The least valuable piece of `color` among `attackers`.
+fn least_valuable_attacker(
+    position: &Position,
+    attackers: Bitboard,
+    color: TeamColor,
+) -> Option<(PieceVariant, usize)> {
+    [
+        PieceVariant::Pawn,
+        PieceVariant::Knight,
+        PieceVariant::Bishop,
+        PieceVariant::Castle,
+        PieceVariant::Queen,
+        PieceVariant::King,
+    ]
+    .into_iter()
+    .find_map(|variant| {
+        let pieces = attackers & position.pieces_of(color, variant);
+        (pieces != 0).then(|| (variant, pieces.trailing_zeros() as usize))
+    })
+}
+
+/// Static exchange evaluation: the material won or lost by `move_data` once
+/// both sides have captured back on its square with their cheapest pieces
+/// for as long as it pays off. Pieces uncovered behind the capturers join
+/// in as well.
+pub fn static_exchange(position: &Position, move_data: BitMove) -> i32 {
+    let (from, to) = (move_data.from as usize, move_data.to as usize);
+    let mut moving_value = match (move_data.variant, position.piece_at(from)) {
+        (MoveType::Promotion(promoted), _) => piece_value(promoted),
+        (_, Some(piece)) => piece_value(piece.variant),
+        (_, None) => return 0,
+    };
+
+    let mut gains = [0; 32];
+    gains[0] = captured_value(position, move_data);
+    if let MoveType::Promotion(promoted) = move_data.variant {
+        gains[0] += piece_value(promoted) - PIECE_VALUES[PieceVariant::Pawn.index()];
+    }
+
+    let mut occupied = position.occupied() ^ (1 << from);
+    if move_data.variant == MoveType::EnPassant {
+        occupied ^= 1 << (to ^ 8);
+    }
+    let mut color = position.side_to_move.opposite();
+    let mut depth = 0;
+    loop {
+        let attackers = (position.attackers(to, TeamColor::White, occupied)
+            | position.attackers(to, TeamColor::Black, occupied))
+            & occupied;
+        let (variant, square) = match least_valuable_attacker(position, attackers, color) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+        // the king may only take back when nothing guards the square
+        let others = attackers & position.occupancy[color.opposite().index()];
+        if variant == PieceVariant::King && others != 0 {
+            break;
+        }
+
+        depth += 1;
+        gains[depth] = moving_value - gains[depth - 1];
+        moving_value = piece_value(variant);
+        occupied ^= 1 << square;
+        color = color.opposite();
+        if depth + 1 == gains.len() {
+            break;
+        }
+    }
+
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+/// What the search learns about quiet moves while it goes: the killers that
+/// caused a cutoff at the same ply, and how often each move did anywhere.
+pub struct MoveOrdering {
+    killers: Vec<[Option<BitMove>; 2]>,
+    history: Vec<[[i32; 64]; 64]>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![[[0; 64]; 64]; 2],
+        }
+    }
+
+    /// Remembers a quiet move that refuted the line at `ply`.
+    pub fn record_cutoff(&mut self, color: TeamColor, move_data: BitMove, ply: usize, depth: u32) {
+        if is_capture(move_data) || matches!(move_data.variant, MoveType::Promotion(_)) {
+            return;
+        }
+
+        let killers = &mut self.killers[ply.min(MAX_PLY - 1)];
+        if killers[0] != Some(move_data) {
+            killers[1] = killers[0];
+            killers[0] = Some(move_data);
+        }
+
+        let history = &mut self.history[color.index()];
+        history[move_data.from as usize][move_data.to as usize] += (depth * depth) as i32;
+        if history[move_data.from as usize][move_data.to as usize] >= HISTORY_LIMIT {
+            // halve everything so the scores keep their order but stay bounded
+            for scores in history.iter_mut() {
+                for score in scores.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+
+    fn score(
+        &self,
+        position: &Position,
+        move_data: BitMove,
+        ply: usize,
+        table_move: Option<BitMove>,
+    ) -> i32 {
+        if Some(move_data) == table_move {
+            return TABLE_MOVE_SCORE;
+        }
+        if is_capture(move_data) || matches!(move_data.variant, MoveType::Promotion(_)) {
+            let base = if static_exchange(position, move_data) >= 0 {
+                GOOD_CAPTURE_SCORE
+            } else {
+                BAD_CAPTURE_SCORE
+            };
+            return base + mvv_lva(position, move_data);
+        }
+
+        let killers = self.killers[ply.min(MAX_PLY - 1)];
+        if let Some(slot) = killers.iter().position(|killer| *killer == Some(move_data)) {
+            return KILLER_SCORES[slot];
+        }
+        self.history[position.side_to_move.index()][move_data.from as usize][move_data.to as usize]
+    }
+
+    /// Sorts `moves` best first: the move from the transposition table,
+    /// captures that do not lose material, killers, quiet moves by history
+    /// and finally captures that do.
+    pub fn order(
+        &self,
+        position: &Position,
+        moves: &mut [BitMove],
+        ply: usize,
+        table_move: Option<BitMove>,
+    ) {
+        moves.sort_by_cached_key(|move_data| -self.score(position, *move_data, ply, table_move));
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::new()
+    }
+}
+
+/// Sorts captures for the quiescence search by MVV-LVA alone.
+pub fn order_captures(position: &Position, moves: &mut [BitMove]) {
+    moves.sort_by_cached_key(|move_data| -mvv_lva(position, *move_data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+    use crate::uci;
+
+    fn exchange(position: &str, uci_move: &str) -> i32 {
+        let (board, data) = fen::parse(position).unwrap();
+        let move_data = uci::parse(&data, uci_move).unwrap();
+        static_exchange(
+            &Position::from_board(&board, &data),
+            BitMove::from_move(&move_data),
+        )
+    }
+
+    #[test]
+    fn exchange_evaluation() {
+        // an undefended pawn
+        assert_eq!(
+            exchange("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // a pawn defended by a pawn, taken with the queen
+        assert_eq!(
+            exchange("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"),
+            100 - 900
+        );
+        // a knight defended once, attacked twice, with the rook behind the
+        // queen only joining after the queen went
+        assert_eq!(
+            exchange("4k3/8/3p4/4n3/8/8/4Q3/4RK2 w - - 0 1", "e2e5"),
+            320 - 900 + 100
+        );
+        // the king cannot take back a guarded piece
+        assert_eq!(exchange("8/8/8/4k3/3p4/8/3R4/3RK3 w - - 0 1", "d2d4"), 100);
+    }
+
+    #[test]
+    fn captures_sort_by_victim_then_attacker() {
+        let position = Position::from_fen("4k3/8/8/3q1r2/2P1P3/8/8/4K2Q w - - 0 1").unwrap();
+        let mut moves = position.generate_legal_captures().to_vec();
+        order_captures(&position, &mut moves);
+
+        let names: Vec<String> = moves
+            .iter()
+            .map(|move_data| uci::render(&move_data.to_move()))
+            .collect();
+        assert_eq!(names[..3], ["c4d5", "e4d5", "e4f5"]);
+    }
+
+    #[test]
+    fn killers_come_before_other_quiet_moves() {
+        let (board, data) = fen::parse(fen::STARTING_POSITION).unwrap();
+        let position = Position::from_board(&board, &data);
+        let killer = BitMove::from_move(&uci::parse(&data, "b1c3").unwrap());
+        let mut ordering = MoveOrdering::new();
+        ordering.record_cutoff(TeamColor::White, killer, 3, 4);
+
+        let mut moves = position.generate_legal_moves().to_vec();
+        ordering.order(&position, &mut moves, 3, None);
+        assert_eq!(moves[0], killer);
+        ordering.order(&position, &mut moves, 4, None);
+        assert_eq!(moves[0], killer, "history still puts it first");
+    }
+}
@@ -0,0 +1,362 @@
+use crate::Error;
+
+use std::fmt;
+use std::fmt::Write;
+
+pub const MIDGAME: usize = 0;
+pub const ENDGAME: usize = 1;
+const PHASE_NAMES: [&str; 2] = ["midgame", "endgame"];
+/// Names of the piece kinds in the order of `PieceVariant::index`.
+const VARIANT_NAMES: [&str; 6] = ["king", "queen", "castle", "bishop", "knight", "pawn"];
+
+/// The weights of the evaluation, each once for the midgame and once for
+/// the endgame, indexed by `MIDGAME` and `ENDGAME`. Scores are in
+/// centipawns and positive values are good for the side that has them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameters {
+    /// Piece values in the order of `PieceVariant::index`.
+    pub material: [[i32; 6]; 2],
+    /// Bonuses per square for each piece kind from White's point of view.
+    /// The tables read like a diagram, rank 8 first.
+    pub piece_squares: [[[i32; 64]; 6]; 2],
+    /// Per square a queen, castle, bishop or knight attacks that is neither
+    /// taken by its own side nor guarded by an enemy pawn, in that order.
+    pub mobility: [[i32; 4]; 2],
+    /// Per square around the enemy king a queen, castle, bishop or knight
+    /// attacks, in that order.
+    pub king_attack: [[i32; 4]; 2],
+    /// Per pawn on the two ranks in front of its own king.
+    pub pawn_shield: [i32; 2],
+    /// Per pawn on a file beyond the first.
+    pub doubled_pawn: [i32; 2],
+    /// Per pawn without pawns of its own side on the neighbouring files.
+    pub isolated_pawn: [i32; 2],
+    /// Per pawn no enemy pawn can stop, by rank counted from its own side.
+    pub passed_pawn: [[i32; 8]; 2],
+}
+
+#[rustfmt::skip]
+const PIECE_SQUARES: [[[i32; 64]; 6]; 2] = [
+    [
+        // king
+        [
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -20, -30, -30, -40, -40, -30, -30, -20,
+            -10, -20, -20, -20, -20, -20, -20, -10,
+             20,  20,   0,   0,   0,   0,  20,  20,
+             20,  30,  10,   0,   0,  10,  30,  20,
+        ],
+        QUEEN_SQUARES,
+        CASTLE_SQUARES,
+        BISHOP_SQUARES,
+        KNIGHT_SQUARES,
+        // pawn
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+             50,  50,  50,  50,  50,  50,  50,  50,
+             10,  10,  20,  30,  30,  20,  10,  10,
+              5,   5,  10,  25,  25,  10,   5,   5,
+              0,   0,   0,  20,  20,   0,   0,   0,
+              5,  -5, -10,   0,   0, -10,  -5,   5,
+              5,  10,  10, -20, -20,  10,  10,   5,
+              0,   0,   0,   0,   0,   0,   0,   0,
+        ],
+    ],
+    [
+        // king: in the endgame it belongs in the middle
+        [
+            -50, -40, -30, -20, -20, -30, -40, -50,
+            -30, -20, -10,   0,   0, -10, -20, -30,
+            -30, -10,  20,  30,  30,  20, -10, -30,
+            -30, -10,  30,  40,  40,  30, -10, -30,
+            -30, -10,  30,  40,  40,  30, -10, -30,
+            -30, -10,  20,  30,  30,  20, -10, -30,
+            -30, -30,   0,   0,   0,   0, -30, -30,
+            -50, -30, -30, -30, -30, -30, -30, -50,
+        ],
+        QUEEN_SQUARES,
+        CASTLE_SQUARES,
+        BISHOP_SQUARES,
+        KNIGHT_SQUARES,
+        // pawn: in the endgame every step forward counts
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+             80,  80,  80,  80,  80,  80,  80,  80,
+             50,  50,  50,  50,  50,  50,  50,  50,
+             30,  30,  30,  30,  30,  30,  30,  30,
+             15,  15,  15,  15,  15,  15,  15,  15,
+              5,   5,   5,   5,   5,   5,   5,   5,
+              0,   0,   0,   0,   0,   0,   0,   0,
+              0,   0,   0,   0,   0,   0,   0,   0,
+        ],
+    ],
+];
+
+#[rustfmt::skip]
+const QUEEN_SQUARES: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const CASTLE_SQUARES: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const BISHOP_SQUARES: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KNIGHT_SQUARES: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+impl Default for Parameters {
+    /// The built-in weights, used for everything a parameter file leaves out.
+    fn default() -> Parameters {
+        Parameters {
+            material: [[0, 900, 500, 330, 320, 100], [0, 950, 520, 320, 290, 130]],
+            piece_squares: PIECE_SQUARES,
+            mobility: [[1, 2, 5, 4], [2, 4, 5, 4]],
+            king_attack: [[5, 3, 2, 2], [2, 1, 1, 1]],
+            pawn_shield: [10, 0],
+            doubled_pawn: [-10, -20],
+            isolated_pawn: [-15, -10],
+            passed_pawn: [
+                [0, 5, 10, 15, 25, 40, 60, 0],
+                [0, 10, 20, 35, 60, 100, 150, 0],
+            ],
+        }
+    }
+}
+
+fn push_phases<'a, const N: usize>(
+    terms: &mut Vec<(String, &'a mut [i32])>,
+    name: &str,
+    values: &'a mut [[i32; N]; 2],
+) {
+    for (values, phase) in values.iter_mut().zip(PHASE_NAMES) {
+        terms.push((format!("{}.{}", name, phase), values));
+    }
+}
+
+fn push_scalar_phases<'a>(
+    terms: &mut Vec<(String, &'a mut [i32])>,
+    name: &str,
+    values: &'a mut [i32; 2],
+) {
+    for (value, phase) in values.iter_mut().zip(PHASE_NAMES) {
+        terms.push((format!("{}.{}", name, phase), std::slice::from_mut(value)));
+    }
+}
+
+impl Parameters {
+    /// Every weight under the name it has in a parameter file, e.g.
+    /// "knight_squares.endgame" or "doubled_pawn.midgame".
+    pub fn terms_mut(&mut self) -> Vec<(String, &mut [i32])> {
+        let Parameters {
+            material,
+            piece_squares,
+            mobility,
+            king_attack,
+            pawn_shield,
+            doubled_pawn,
+            isolated_pawn,
+            passed_pawn,
+        } = self;
+
+        let mut terms = vec![];
+        push_phases(&mut terms, "material", material);
+        for (tables, phase) in piece_squares.iter_mut().zip(PHASE_NAMES) {
+            for (table, variant) in tables.iter_mut().zip(VARIANT_NAMES) {
+                terms.push((format!("{}_squares.{}", variant, phase), &mut table[..]));
+            }
+        }
+        push_phases(&mut terms, "mobility", mobility);
+        push_phases(&mut terms, "king_attack", king_attack);
+        push_scalar_phases(&mut terms, "pawn_shield", pawn_shield);
+        push_scalar_phases(&mut terms, "doubled_pawn", doubled_pawn);
+        push_scalar_phases(&mut terms, "isolated_pawn", isolated_pawn);
+        push_phases(&mut terms, "passed_pawn", passed_pawn);
+        terms
+    }
+}
+
+/// What is wrong with a parameter file.
+#[derive(Debug, PartialEq)]
+pub enum ParameterError {
+    ValueWithoutName(String),
+    UnknownName(String),
+    ValueCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterError::ValueWithoutName(value) => {
+                write!(f, "value {} does not follow a parameter name", value)
+            }
+            ParameterError::UnknownName(name) => write!(f, "unknown parameter '{}'", name),
+            ParameterError::ValueCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "parameter '{}' takes {} values, found {}",
+                name, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+/// Reads a parameter file: every name is followed by its values, separated
+/// by any whitespace including line breaks, and `#` starts a comment that
+/// runs to the end of the line. Weights the file does not name keep their
+/// built-in value.
+pub fn parse(text: &str) -> Result<Parameters, ParameterError> {
+    let mut given: Vec<(String, Vec<i32>)> = vec![];
+    let tokens = text
+        .lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace());
+    for token in tokens {
+        match token.parse::<i32>() {
+            Ok(value) => given
+                .last_mut()
+                .ok_or_else(|| ParameterError::ValueWithoutName(token.to_string()))?
+                .1
+                .push(value),
+            Err(_) => given.push((token.to_string(), vec![])),
+        }
+    }
+
+    let mut parameters = Parameters::default();
+    let mut terms = parameters.terms_mut();
+    for (name, values) in given {
+        let term = match terms.iter_mut().find(|(term_name, _)| *term_name == name) {
+            Some((_, term)) => term,
+            None => return Err(ParameterError::UnknownName(name)),
+        };
+        if values.len() != term.len() {
+            return Err(ParameterError::ValueCount {
+                expected: term.len(),
+                found: values.len(),
+                name,
+            });
+        }
+        term.copy_from_slice(&values);
+    }
+    Ok(parameters)
+}
+
+/// Writes `parameters` in the format `parse` reads, the piece-square tables
+/// as diagrams with rank 8 on top.
+pub fn render(parameters: &Parameters) -> String {
+    let mut text =
+        String::from("# evaluation parameters, midgame and endgame weights in centipawns\n");
+    for (name, values) in parameters.clone().terms_mut() {
+        if values.len() == 64 {
+            writeln!(text, "{}", name).unwrap();
+            for rank in values.chunks(8) {
+                for value in rank {
+                    write!(text, "{:>5}", value).unwrap();
+                }
+                writeln!(text).unwrap();
+            }
+        } else {
+            write!(text, "{}", name).unwrap();
+            for value in values.iter() {
+                write!(text, " {}", value).unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+    }
+    text
+}
+
+pub fn load(path: &str) -> Result<Parameters, Error> {
+    Ok(parse(&std::fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendered_parameters_parse_back() {
+        let mut parameters = Parameters::default();
+        parameters.piece_squares[ENDGAME][4][27] = -7;
+        parameters.doubled_pawn[MIDGAME] = 3;
+        assert_eq!(parse(&render(&parameters)), Ok(parameters));
+    }
+
+    #[test]
+    fn missing_weights_keep_their_default() {
+        let parameters =
+            parse("# a partial file\nisolated_pawn.endgame -40\nmobility.midgame 1 2\n 3 4")
+                .unwrap();
+        assert_eq!(parameters.isolated_pawn, [-15, -40]);
+        assert_eq!(parameters.mobility[MIDGAME], [1, 2, 3, 4]);
+        assert_eq!(parameters.material, Parameters::default().material);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert_eq!(
+            parse("12 material.midgame"),
+            Err(ParameterError::ValueWithoutName("12".to_string()))
+        );
+        assert_eq!(
+            parse("rook_squares.midgame 1"),
+            Err(ParameterError::UnknownName(
+                "rook_squares.midgame".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("passed_pawn.endgame 1 2 3"),
+            Err(ParameterError::ValueCount {
+                name: "passed_pawn.endgame".to_string(),
+                expected: 8,
+                found: 3,
+            })
+        );
+    }
+}
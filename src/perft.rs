@@ -0,0 +1,181 @@
+use crate::bitboard::Position;
+use crate::game::{Board, GameData};
+use crate::produce;
+use crate::uci;
+
+/// Counts the leaf nodes of the legal move tree `depth` plies deep.
+pub fn perft(board: &mut Board, data: &mut GameData, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = produce::generate_legal_moves(board, data);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for move_data in moves {
+        let undo = produce::make_move(board, data, &move_data);
+        nodes += perft(board, data, depth - 1);
+        produce::unmake_move(board, data, &undo);
+    }
+    nodes
+}
+
+/// Splits the perft count by root move, each written in UCI notation, so a
+/// wrong total can be narrowed down to the move it comes from.
+pub fn divide(board: &mut Board, data: &mut GameData, depth: u32) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = produce::generate_legal_moves(board, data)
+        .into_iter()
+        .map(|move_data| {
+            let undo = produce::make_move(board, data, &move_data);
+            let nodes = perft(board, data, depth.saturating_sub(1));
+            produce::unmake_move(board, data, &undo);
+            (uci::render(&move_data), nodes)
+        })
+        .collect();
+
+    counts.sort();
+    counts
+}
+
+/// The same count as `perft`, walked with the bitboard move generator.
+pub fn perft_position(position: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = position.generate_legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves
+        .iter()
+        .map(|move_data| perft_position(&position.make_move(*move_data), depth - 1))
+        .sum()
+}
+
+/// The same split as `divide`, walked with the bitboard move generator.
+pub fn divide_position(position: &Position, depth: u32) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = position
+        .generate_legal_moves()
+        .iter()
+        .map(|move_data| {
+            let nodes = perft_position(&position.make_move(*move_data), depth.saturating_sub(1));
+            (uci::render(&move_data.to_move()), nodes)
+        })
+        .collect();
+
+    counts.sort();
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let (mut board, mut data) = fen::parse(fen).unwrap();
+        let before = fen::serialize(&board, &data);
+
+        for (depth, nodes) in expected.iter().enumerate() {
+            let depth = depth as u32 + 1;
+            assert_eq!(
+                perft(&mut board, &mut data, depth),
+                *nodes,
+                "{} at depth {}",
+                fen,
+                depth
+            );
+        }
+        assert_eq!(fen::serialize(&board, &data), before);
+
+        let position = Position::from_board(&board, &data);
+        for (depth, nodes) in expected.iter().enumerate() {
+            let depth = depth as u32 + 1;
+            assert_eq!(
+                perft_position(&position, depth),
+                *nodes,
+                "{} at depth {}",
+                fen,
+                depth
+            );
+        }
+    }
+
+    #[test]
+    fn starting_position() {
+        assert_perft(fen::STARTING_POSITION, &[20, 400, 8_902, 197_281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2_039, 97_862]);
+    }
+
+    #[test]
+    fn position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2_812, 43_238]);
+    }
+
+    #[test]
+    fn position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9_467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9_467]);
+    }
+
+    #[test]
+    fn position_5() {
+        assert_perft(POSITION_5, &[44, 1_486, 62_379]);
+    }
+
+    #[test]
+    fn position_6() {
+        assert_perft(POSITION_6, &[46, 2_079, 89_890]);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let (mut board, mut data) = fen::parse(KIWIPETE).unwrap();
+        let counts = divide(&mut board, &mut data, 2);
+
+        assert_eq!(counts.len(), 48);
+        assert!(counts.contains(&("e1g1".to_string(), 43)));
+        assert_eq!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2_039);
+    }
+
+    #[test]
+    fn divide_matches_between_generators() {
+        let (mut board, mut data) = fen::parse(POSITION_5).unwrap();
+        let position = Position::from_board(&board, &data);
+
+        assert_eq!(
+            divide_position(&position, 3),
+            divide(&mut board, &mut data, 3)
+        );
+    }
+
+    #[test]
+    fn unmake_move_restores_position() {
+        let (mut board, mut data) = fen::parse(POSITION_4).unwrap();
+        let before = fen::serialize(&board, &data);
+
+        for move_data in produce::generate_legal_moves(&board, &data) {
+            let undo = produce::make_move(&mut board, &mut data, &move_data);
+            produce::unmake_move(&mut board, &mut data, &undo);
+            assert_eq!(fen::serialize(&board, &data), before, "{:?}", move_data);
+        }
+    }
+}
@@ -0,0 +1,651 @@
+use crate::amend;
+use crate::fen::{self, FenError};
+use crate::game::{Board, Game, GameData, GameResult, GameState};
+use crate::san::{self, SanError};
+use crate::Error;
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn result_token(game: &Game) -> &'static str {
+    match game.state {
+        GameState::GameOver(GameResult::WhiteWins, _) => "1-0",
+        GameState::GameOver(GameResult::BlackWins, _) => "0-1",
+        GameState::GameOver(GameResult::Draw, _) => "1/2-1/2",
+        _ => "*",
+    }
+}
+
+/// Today's date in the "YYYY.MM.DD" form of the Date tag.
+fn current_date() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+
+    // days since the epoch to a proleptic gregorian date
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Joins the movetext tokens into lines no longer than 79 characters.
+fn wrap_movetext(tokens: &[String]) -> String {
+    let mut movetext = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > 79 {
+            movetext.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            movetext.push(' ');
+            line_length += 1;
+        }
+        movetext.push_str(token);
+        line_length += token.len();
+    }
+    movetext
+}
+
+/// Writes the game played so far in PGN export format, with the Seven Tag
+/// Roster and the SetUp/FEN tags when the game started from a custom position.
+pub fn export(game: &Game) -> String {
+    write_game(&game.cache.data, result_token(game))
+}
+
+fn write_game(data: &GameData, result: &str) -> String {
+    let mut pgn = String::new();
+    for (name, value) in [
+        ("Event", "Casual game".to_string()),
+        ("Site", "?".to_string()),
+        ("Date", current_date()),
+        ("Round", "-".to_string()),
+        ("White", "?".to_string()),
+        ("Black", "?".to_string()),
+        ("Result", result.to_string()),
+    ] {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    if data.initial_position != fen::STARTING_POSITION {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", data.initial_position));
+    }
+    pgn.push('\n');
+
+    let fields: Vec<&str> = data.initial_position.split_whitespace().collect();
+    let mut is_white_to_move = fields.get(1) != Some(&"b");
+    let mut move_number: u32 = fields
+        .get(5)
+        .and_then(|field| field.parse().ok())
+        .unwrap_or(1);
+
+    let mut tokens = vec![];
+    for (i, san) in data.move_list.iter().enumerate() {
+        if is_white_to_move {
+            tokens.push(format!("{}.", move_number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(san.clone());
+
+        if !is_white_to_move {
+            move_number += 1;
+        }
+        is_white_to_move = !is_white_to_move;
+    }
+    tokens.push(result.to_string());
+
+    pgn.push_str(&wrap_movetext(&tokens));
+    pgn.push('\n');
+    pgn
+}
+
+/// Saves the game to a new PGN file in the working directory and returns
+/// the path it was written to.
+pub fn save(game: &Game) -> Result<String, Error> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = format!("chess-{}.pgn", timestamp);
+    std::fs::write(&path, export(game))?;
+    Ok(path)
+}
+
+/// A game read from a PGN file, with its movetext reduced to the main line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    UnterminatedTag(usize),
+    MalformedTag(usize),
+    UnterminatedComment(usize),
+    UnbalancedVariation(usize),
+    UnexpectedCharacter { line: usize, character: char },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::UnterminatedTag(line) => write!(f, "line {}: tag pair is never closed", line),
+            PgnError::MalformedTag(line) => write!(f, "line {}: malformed tag pair", line),
+            PgnError::UnterminatedComment(line) => {
+                write!(f, "line {}: comment is never closed", line)
+            }
+            PgnError::UnbalancedVariation(line) => {
+                write!(f, "line {}: unbalanced variation parentheses", line)
+            }
+            PgnError::UnexpectedCharacter { line, character } => {
+                write!(f, "line {}: unexpected character '{}'", line, character)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Symbol(String),
+    Nag,
+    Comment,
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+fn is_symbol_character(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/.!?".contains(c)
+}
+
+fn parse_tag(text: &str, line: usize) -> Result<Token, PgnError> {
+    let text = text.trim();
+    let name_end = text
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    let (name, rest) = text.split_at(name_end);
+    let rest = rest.trim();
+
+    if name.is_empty() || rest.len() < 2 || !rest.starts_with('"') || !rest.ends_with('"') {
+        return Err(PgnError::MalformedTag(line));
+    }
+
+    let mut value = String::new();
+    let mut chars = rest[1..rest.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+
+    Ok(Token::Tag(name.to_string(), value))
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, PgnError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    let mut is_line_start = true;
+
+    while let Some(c) = chars.next() {
+        let token_line = line;
+        let was_line_start = is_line_start;
+        is_line_start = c == '\n';
+
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // escaped lines and rest-of-line comments
+            '%' | ';' if c == ';' || was_line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        is_line_start = true;
+                        break;
+                    }
+                }
+                if c == ';' {
+                    Token::Comment
+                } else {
+                    continue;
+                }
+            }
+            '{' => {
+                let mut is_closed = false;
+                for c in chars.by_ref() {
+                    match c {
+                        '}' => {
+                            is_closed = true;
+                            break;
+                        }
+                        '\n' => line += 1,
+                        _ => (),
+                    }
+                }
+                if !is_closed {
+                    return Err(PgnError::UnterminatedComment(token_line));
+                }
+                Token::Comment
+            }
+            '[' => {
+                let mut tag = String::new();
+                let mut is_quoted = false;
+                let mut is_closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' if is_quoted => {
+                            tag.push(c);
+                            tag.extend(chars.next());
+                        }
+                        '"' => {
+                            is_quoted = !is_quoted;
+                            tag.push(c);
+                        }
+                        ']' if !is_quoted => {
+                            is_closed = true;
+                            break;
+                        }
+                        '\n' => return Err(PgnError::UnterminatedTag(token_line)),
+                        c => tag.push(c),
+                    }
+                }
+                if !is_closed {
+                    return Err(PgnError::UnterminatedTag(token_line));
+                }
+                parse_tag(&tag, token_line)?
+            }
+            '(' => Token::VariationStart,
+            ')' => Token::VariationEnd,
+            '*' => Token::Result("*".to_string()),
+            '$' => {
+                while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    chars.next();
+                }
+                Token::Nag
+            }
+            c if is_symbol_character(c) => {
+                let mut symbol = c.to_string();
+                while let Some(&c) = chars.peek().filter(|&&c| is_symbol_character(c)) {
+                    symbol.push(c);
+                    chars.next();
+                }
+                match symbol.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" => Token::Result(symbol),
+                    _ => Token::Symbol(symbol),
+                }
+            }
+            character => {
+                return Err(PgnError::UnexpectedCharacter {
+                    line: token_line,
+                    character,
+                })
+            }
+        };
+        tokens.push((token, token_line));
+    }
+
+    Ok(tokens)
+}
+
+/// Strips a leading move number indication such as "12." or "12..." from a
+/// symbol, leaving whatever move was written right after it.
+fn strip_move_number(symbol: &str) -> &str {
+    let digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if digits.len() < symbol.len() && digits.starts_with('.') {
+        digits.trim_start_matches('.')
+    } else if digits.is_empty() {
+        ""
+    } else {
+        symbol
+    }
+}
+
+/// Reads every game of a PGN file. Comments, NAGs and variations are
+/// skipped so that only the main line of each game is kept.
+pub fn parse_games(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = vec![];
+    let mut current = PgnGame::default();
+    let mut has_movetext = false;
+    let mut variation_depth = 0;
+
+    for (token, line) in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => {
+                if variation_depth > 0 {
+                    return Err(PgnError::UnbalancedVariation(line));
+                }
+                if has_movetext {
+                    games.push(std::mem::take(&mut current));
+                    has_movetext = false;
+                }
+                current.tags.push((name, value));
+            }
+            Token::VariationStart => variation_depth += 1,
+            Token::VariationEnd if variation_depth == 0 => {
+                return Err(PgnError::UnbalancedVariation(line))
+            }
+            Token::VariationEnd => variation_depth -= 1,
+            _ if variation_depth > 0 => (),
+            Token::Result(result) => {
+                current.result = result;
+                games.push(std::mem::take(&mut current));
+                has_movetext = false;
+            }
+            Token::Symbol(symbol) => {
+                has_movetext = true;
+                let san = strip_move_number(&symbol);
+                if !san.is_empty() {
+                    current.moves.push(san.to_string());
+                }
+            }
+            Token::Nag | Token::Comment => (),
+        }
+    }
+
+    if variation_depth > 0 {
+        return Err(PgnError::UnbalancedVariation(text.lines().count()));
+    }
+    if has_movetext || !current.tags.is_empty() {
+        current.result = current.tag("Result").unwrap_or("*").to_string();
+        games.push(current);
+    }
+
+    Ok(games)
+}
+
+/// Why replaying a game stopped before the end of its main line.
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    InvalidFen(FenError),
+    InvalidMove { ply: usize, error: SanError },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            ReplayError::InvalidMove { ply, error } => {
+                write!(f, "stopped at move {}: {}", ply, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Games loaded from a PGN file together with the positions of the one
+/// being stepped through on the board.
+pub struct Replay {
+    pub games: Vec<PgnGame>,
+    pub game_index: usize,
+    pub positions: Vec<(Board, GameData)>,
+    /// Why the positions end before the last move of the game, if they do.
+    pub error: Option<ReplayError>,
+    pub ply: usize,
+}
+
+impl Replay {
+    pub fn new(games: Vec<PgnGame>) -> Replay {
+        let (positions, error) = games.first().map(replay_positions).unwrap_or_default();
+        Replay {
+            games,
+            game_index: 0,
+            positions,
+            error,
+            ply: 0,
+        }
+    }
+
+    pub fn select_game(&mut self, game_index: usize) {
+        if let Some(pgn_game) = self.games.get(game_index) {
+            (self.positions, self.error) = replay_positions(pgn_game);
+            self.game_index = game_index;
+            self.ply = 0;
+        }
+    }
+}
+
+/// Plays the main line of `pgn_game` from its starting position and returns
+/// every position reached. Replaying stops at the first move that cannot be
+/// resolved, so the positions up to it can still be looked at, and the
+/// reason is returned alongside them.
+pub fn replay_positions(pgn_game: &PgnGame) -> (Vec<(Board, GameData)>, Option<ReplayError>) {
+    let initial_position = pgn_game.tag("FEN").unwrap_or(fen::STARTING_POSITION);
+    let (mut board, mut data) = match fen::parse(initial_position) {
+        Ok(position) => position,
+        Err(error) => return (vec![], Some(ReplayError::InvalidFen(error))),
+    };
+
+    let mut positions = vec![(board, data.clone())];
+    for san in pgn_game.moves.iter() {
+        let move_data = match san::parse(&board, &data, san) {
+            Ok(move_data) => move_data,
+            Err(error) => {
+                let ply = positions.len();
+                return (positions, Some(ReplayError::InvalidMove { ply, error }));
+            }
+        };
+        amend::apply_move(&mut board, &mut data, &move_data);
+        amend::begin_turn(&board, &mut data);
+        positions.push((board, data.clone()));
+    }
+
+    (positions, None)
+}
+
+pub fn load_replay(path: &str) -> Result<Replay, Error> {
+    let games = parse_games(&std::fs::read_to_string(path)?)?;
+    if games.is_empty() {
+        return Err(format!("no games found in {}", path).into());
+    }
+    Ok(Replay::new(games))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(moves: &[&str]) -> Vec<String> {
+        moves.iter().map(|san| san.to_string()).collect()
+    }
+
+    #[test]
+    fn tokenizes_every_kind_of_token() {
+        let text = "[Event \"Club \\\"open\\\"\"]\n\
+                    % an escaped line ; that hides a comment\n\
+                    1. e4 $1 {a brace\ncomment} e5 ; to the end of the line\n\
+                    2. Nf3 (2. f4 exf4 (2... d5)) Nc6 1/2-1/2 *";
+        let tokens: Vec<Token> = tokenize(text)
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Tag("Event".to_string(), "Club \"open\"".to_string()),
+                Token::Symbol("1.".to_string()),
+                Token::Symbol("e4".to_string()),
+                Token::Nag,
+                Token::Comment,
+                Token::Symbol("e5".to_string()),
+                Token::Comment,
+                Token::Symbol("2.".to_string()),
+                Token::Symbol("Nf3".to_string()),
+                Token::VariationStart,
+                Token::Symbol("2.".to_string()),
+                Token::Symbol("f4".to_string()),
+                Token::Symbol("exf4".to_string()),
+                Token::VariationStart,
+                Token::Symbol("2...".to_string()),
+                Token::Symbol("d5".to_string()),
+                Token::VariationEnd,
+                Token::VariationEnd,
+                Token::Symbol("Nc6".to_string()),
+                Token::Result("1/2-1/2".to_string()),
+                Token::Result("*".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tracks_line_numbers() {
+        let lines: Vec<usize> = tokenize("{one\ntwo} e4\n; comment\n% escape\n\ne5")
+            .unwrap()
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect();
+        assert_eq!(lines, [1, 2, 3, 6]);
+    }
+
+    #[test]
+    fn keeps_the_main_line_of_every_game() {
+        let text = "[White \"A\"]\n[Result \"1-0\"]\n\n\
+                    1.e4 {best by test} e5 2.Nf3 $2 (2.f4 (2.Nc3) exf4) 2...Nc6 1-0\n\n\
+                    [White \"B\"]\n\n1. d4 d5 0-1\n\n\
+                    [White \"C\"]\n[Result \"1/2-1/2\"]\n\n1. c4";
+        let games = parse_games(text).unwrap();
+        assert_eq!(games.len(), 3);
+
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].moves, symbols(&["e4", "e5", "Nf3", "Nc6"]));
+        assert_eq!(games[0].result, "1-0");
+
+        assert_eq!(games[1].tag("White"), Some("B"));
+        assert_eq!(games[1].moves, symbols(&["d4", "d5"]));
+        assert_eq!(games[1].result, "0-1");
+
+        // without a result token the Result tag is used
+        assert_eq!(games[2].moves, symbols(&["c4"]));
+        assert_eq!(games[2].result, "1/2-1/2");
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let errors = [
+            ("[Event \"open", PgnError::UnterminatedTag(1)),
+            ("[Event \"a\"\n]", PgnError::UnterminatedTag(1)),
+            ("[Event]", PgnError::MalformedTag(1)),
+            ("[\"a\"]", PgnError::MalformedTag(1)),
+            ("1. e4\n{never closed", PgnError::UnterminatedComment(2)),
+            ("1. e4 (1. d4\n(1. c4) e5", PgnError::UnbalancedVariation(2)),
+            ("1. e4 e5)\n", PgnError::UnbalancedVariation(1)),
+            (
+                "1. e4 (1. d4\n[Event \"a\"]",
+                PgnError::UnbalancedVariation(2),
+            ),
+            (
+                "1. e4\n\n2. @",
+                PgnError::UnexpectedCharacter {
+                    line: 3,
+                    character: '@',
+                },
+            ),
+        ];
+        for (text, error) in errors {
+            assert_eq!(parse_games(text), Err(error), "{}", text);
+        }
+    }
+
+    /// Plays `moves` from `initial_position` the way the game does.
+    fn play(initial_position: &str, moves: &[&str]) -> GameData {
+        let (mut board, mut data) = fen::parse(initial_position).unwrap();
+        for text in moves {
+            let move_data = san::parse(&board, &data, text).unwrap();
+            amend::apply_move(&mut board, &mut data, &move_data);
+            amend::begin_turn(&board, &mut data);
+        }
+        data
+    }
+
+    #[test]
+    fn exported_games_read_back_the_same() {
+        for (initial_position, moves, result) in [
+            (
+                fen::STARTING_POSITION,
+                &[
+                    "e4", "d5", "e5", "f5", "exf6", "Nc6", "fxg7", "Bf5", "gxh8=Q", "Qd7", "Nf3",
+                    "O-O-O", "Bb5", "a6", "O-O",
+                ][..],
+                "*",
+            ),
+            (
+                "8/4P3/8/8/8/8/k7/4K3 b - - 3 40",
+                &["Kb2", "e8=Q", "Kb1", "Qb5+", "Ka1", "Qb4"][..],
+                "1-0",
+            ),
+        ] {
+            let data = play(initial_position, moves);
+            let pgn = write_game(&data, result);
+
+            let games = parse_games(&pgn).unwrap();
+            assert_eq!(games.len(), 1);
+            let pgn_game = &games[0];
+            assert_eq!(pgn_game.moves, data.move_list, "{}", pgn);
+            assert_eq!(pgn_game.result, result);
+            assert_eq!(pgn_game.tag("Result"), Some(result));
+            let is_custom = initial_position != fen::STARTING_POSITION;
+            assert_eq!(pgn_game.tag("FEN").is_some(), is_custom);
+
+            let (positions, error) = replay_positions(pgn_game);
+            assert_eq!(error, None);
+            assert_eq!(positions.len(), moves.len() + 1);
+            let (_, last) = positions.last().unwrap();
+            assert_eq!(last.hash, data.hash);
+        }
+    }
+
+    #[test]
+    fn replay_stops_at_the_first_bad_move() {
+        let pgn_game = PgnGame {
+            moves: symbols(&["e4", "e5", "Ke3", "Nf6"]),
+            ..PgnGame::default()
+        };
+        let (positions, error) = replay_positions(&pgn_game);
+        assert_eq!(positions.len(), 3);
+        assert_eq!(
+            error,
+            Some(ReplayError::InvalidMove {
+                ply: 3,
+                error: SanError::Illegal("Ke3".to_string()),
+            })
+        );
+
+        let pgn_game = PgnGame {
+            tags: vec![("FEN".to_string(), "8/8/8/8/8/8/8/K7 w - - 0 1".to_string())],
+            ..PgnGame::default()
+        };
+        let (positions, error) = replay_positions(&pgn_game);
+        assert!(positions.is_empty());
+        assert!(matches!(error, Some(ReplayError::InvalidFen(_))));
+    }
+}
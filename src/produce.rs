@@ -1,15 +1,16 @@
-use crate::game::{Game, PieceVariant};
+use crate::game::{Board, Game, GameData, Piece, PieceVariant, TeamColor};
+use crate::zobrist;
 
 use std::collections::HashSet;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Move {
     pub variant: MoveType,
     pub from: (usize, usize),
     pub to: (usize, usize),
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum MoveType {
     Capture,
     NonCapture,
@@ -19,8 +20,13 @@ pub enum MoveType {
     EnPassant,
 }
 
+fn get_square(board: &Board, column: usize, row: usize) -> Option<&Piece> {
+    board.get(row)?.get(column)?.as_ref()
+}
+
 fn generate_sliding_moves(
-    game: &Game,
+    board: &Board,
+    color: TeamColor,
     from: (usize, usize),
     offset_range: (usize, usize),
 ) -> HashSet<Move> {
@@ -48,9 +54,9 @@ fn generate_sliding_moves(
 
             let (target_column, target_row) = (target_column as usize, target_row as usize);
 
-            let target_square = game.get_square(target_column, target_row);
+            let target_square = get_square(board, target_column, target_row);
             match target_square {
-                Some(piece) if game.cache.data.current_turn == piece.color => break,
+                Some(piece) if color == piece.color => break,
                 Some(_) => {
                     moves.insert(Move {
                         variant: MoveType::Capture,
@@ -72,16 +78,14 @@ fn generate_sliding_moves(
     moves
 }
 
-fn generate_king_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
+fn generate_king_moves(board: &Board, color: TeamColor, from: (usize, usize)) -> HashSet<Move> {
     let mut moves: HashSet<Move> = HashSet::new();
-    let is_ally = game.cache.data.current_turn == game.cache.data.player_color;
 
     let king_directions = [
         (-1, -1),
         (-1, 0),
         (-1, 1),
         (0, -1),
-        (0, 0),
         (0, 1),
         (1, -1),
         (1, 0),
@@ -98,8 +102,8 @@ fn generate_king_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
 
         let (target_column, target_row) = (target_column as usize, target_row as usize);
 
-        match game.get_square(target_column, target_row) {
-            Some(piece) if game.cache.data.current_turn == piece.color => continue,
+        match get_square(board, target_column, target_row) {
+            Some(piece) if color == piece.color => continue,
             square => moves.insert(Move {
                 from,
                 to: (target_column, target_row),
@@ -112,53 +116,62 @@ fn generate_king_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
         };
     }
 
-    let castling_directions = [-1, 1];
-    let castling_ptr = if is_ally { 0 } else { 1 };
-    let mut is_valid_castling = [false, false];
+    moves
+}
 
-    for (i, dir) in castling_directions.iter().enumerate() {
-        let mut j: i32 = 0;
-        loop {
-            j += 1;
-            let target_column = from.0 as i32 + dir * j;
-            if !(0..8).contains(&target_column) {
-                break;
-            }
+fn generate_castling_moves(
+    board: &Board,
+    data: &GameData,
+    color: TeamColor,
+    from: (usize, usize),
+) -> HashSet<Move> {
+    let mut moves: HashSet<Move> = HashSet::new();
+    let castling_ptr = color.index();
+    let home_row = color.home_row();
+    let home_column = 4;
 
-            let target_column = target_column as usize;
-            match game.get_square(target_column, from.1) {
-                Some(piece)
-                    if piece.variant == PieceVariant::Castle && [0, 7].contains(&target_column) =>
-                {
-                    is_valid_castling[i] = true;
-                    break;
-                }
-                Some(_) => break,
-                None => continue,
-            }
-        }
+    // the king must stand on its home square and may not castle out of check
+    if from != (home_column, home_row) || is_square_attacked(board, data, from, color.opposite()) {
+        return moves;
     }
 
-    if is_valid_castling[0] && game.cache.data.is_valid_castling[castling_ptr][0] {
-        moves.insert(Move {
-            variant: MoveType::Castling(0),
-            from,
-            to: (from.0 - 2, from.1),
-        });
-    }
+    for (column_ptr, rook_column) in [0, 7].into_iter().enumerate() {
+        if !data.is_valid_castling[castling_ptr][column_ptr] {
+            continue;
+        }
 
-    if is_valid_castling[1] && game.cache.data.is_valid_castling[castling_ptr][1] {
-        moves.insert(Move {
-            variant: MoveType::Castling(7),
-            from,
-            to: (from.0 + 2, from.1),
-        });
+        let is_own_rook = get_square(board, rook_column, home_row)
+            .is_some_and(|piece| piece.variant == PieceVariant::Castle && piece.color == color);
+        let (low, high) = if rook_column < from.0 {
+            (rook_column + 1, from.0)
+        } else {
+            (from.0 + 1, rook_column)
+        };
+        let is_path_empty = (low..high).all(|column| get_square(board, column, home_row).is_none());
+
+        // the king may neither pass through nor land on an attacked square
+        let to_column = if rook_column < from.0 {
+            from.0 - 2
+        } else {
+            from.0 + 2
+        };
+        let is_path_safe = [(from.0 + to_column) / 2, to_column]
+            .into_iter()
+            .all(|column| !is_square_attacked(board, data, (column, home_row), color.opposite()));
+
+        if is_own_rook && is_path_empty && is_path_safe {
+            moves.insert(Move {
+                variant: MoveType::Castling(rook_column),
+                from,
+                to: (to_column, home_row),
+            });
+        }
     }
 
     moves
 }
 
-fn generate_knight_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
+fn generate_knight_moves(board: &Board, color: TeamColor, from: (usize, usize)) -> HashSet<Move> {
     let mut moves: HashSet<Move> = HashSet::new();
 
     let knight_directions = [
@@ -181,10 +194,10 @@ fn generate_knight_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
         }
 
         let (target_column, target_row) = (target_column as usize, target_row as usize);
-        let target_square = game.get_square(target_column, target_row);
+        let target_square = get_square(board, target_column, target_row);
 
         match target_square {
-            Some(piece) if game.cache.data.current_turn == piece.color => continue,
+            Some(piece) if color == piece.color => continue,
             square => moves.insert(Move {
                 variant: if square.is_none() {
                     MoveType::NonCapture
@@ -215,12 +228,17 @@ fn generate_pawn_promotion_moves(
     true
 }
 
-fn generate_pawn_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
+fn generate_pawn_moves(
+    board: &Board,
+    data: &GameData,
+    color: TeamColor,
+    from: (usize, usize),
+) -> HashSet<Move> {
     let mut moves = HashSet::new();
-    let is_ally = game.cache.data.current_turn == game.cache.data.player_color;
-    let start_row = if !is_ally { 1 } else { 6 };
-    let pawn_end_row = if !is_ally { 7 } else { 0 };
-    let pawn_forward_offset = if !is_ally { 1 } else { -1 };
+    let is_white = color == TeamColor::White;
+    let start_row = if is_white { 1 } else { 6 };
+    let pawn_end_row = if is_white { 7 } else { 0 };
+    let pawn_forward_offset = if is_white { 1 } else { -1 };
     let pawn_forward_limit = if start_row == from.1 { 2 } else { 1 };
 
     let mut i: u32 = 1;
@@ -234,7 +252,7 @@ fn generate_pawn_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
         }
 
         let target_row = target_row as usize;
-        let target_square = game.get_square(from.0, target_row);
+        let target_square = get_square(board, from.0, target_row);
         match target_square {
             Some(_) => break,
             None if pawn_end_row == target_row => {
@@ -263,12 +281,14 @@ fn generate_pawn_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
         }
 
         let (target_column, target_row) = (target_column as usize, target_row as usize);
-        let is_move_enpassant =
-            Some((target_column, from.1)) == game.cache.data.recent_advancing_pawn;
-        let target_square = game.get_square(target_column, target_row);
+        let is_move_enpassant = Some((target_column, from.1)) == data.recent_advancing_pawn;
+        let target_square = get_square(board, target_column, target_row);
 
         match target_square {
-            Some(piece) if game.cache.data.current_turn != piece.color => moves.insert(Move {
+            Some(piece) if color != piece.color && pawn_end_row == target_row => {
+                generate_pawn_promotion_moves(&mut moves, from, (target_column, target_row))
+            }
+            Some(piece) if color != piece.color => moves.insert(Move {
                 variant: MoveType::Capture,
                 from,
                 to: (target_column, target_row),
@@ -285,14 +305,15 @@ fn generate_pawn_moves(game: &Game, from: (usize, usize)) -> HashSet<Move> {
     moves
 }
 
-pub fn generate_moves(game: &Game) -> HashSet<Move> {
+fn generate_pseudo_legal_moves(board: &Board, data: &GameData) -> HashSet<Move> {
     use PieceVariant::*;
 
     let mut moves: HashSet<Move> = HashSet::new();
+    let color = data.current_turn;
 
     for row in 0..8 {
         for column in 0..8 {
-            let square = game.get_square(column, row);
+            let square = get_square(board, column, row);
 
             if square.is_none() {
                 continue;
@@ -300,17 +321,21 @@ pub fn generate_moves(game: &Game) -> HashSet<Move> {
 
             let piece = square.unwrap();
 
-            if piece.color != game.cache.data.current_turn {
+            if piece.color != color {
                 continue;
             }
 
             let piece_moves = match piece.variant {
-                Queen => generate_sliding_moves(game, (column, row), (0, 8)),
-                Castle => generate_sliding_moves(game, (column, row), (0, 4)),
-                Bishop => generate_sliding_moves(game, (column, row), (4, 8)),
-                King => generate_king_moves(game, (column, row)),
-                Knight => generate_knight_moves(game, (column, row)),
-                Pawn => generate_pawn_moves(game, (column, row)),
+                Queen => generate_sliding_moves(board, color, (column, row), (0, 8)),
+                Castle => generate_sliding_moves(board, color, (column, row), (0, 4)),
+                Bishop => generate_sliding_moves(board, color, (column, row), (4, 8)),
+                King => {
+                    let mut king_moves = generate_king_moves(board, color, (column, row));
+                    king_moves.extend(generate_castling_moves(board, data, color, (column, row)));
+                    king_moves
+                }
+                Knight => generate_knight_moves(board, color, (column, row)),
+                Pawn => generate_pawn_moves(board, data, color, (column, row)),
             };
 
             moves.extend(piece_moves)
@@ -319,3 +344,304 @@ pub fn generate_moves(game: &Game) -> HashSet<Move> {
 
     moves
 }
+
+/// Moves the pieces of `move_data` on `board` without touching any game data,
+/// including the rook of a castling move and the pawn taken en passant.
+pub fn play_move(board: &mut Board, move_data: &Move) {
+    let piece_taken = board[move_data.from.1][move_data.from.0].take();
+
+    board[move_data.to.1][move_data.to.0] = match move_data.variant {
+        MoveType::Promotion(variant) => piece_taken.map(|piece| Piece {
+            variant,
+            color: piece.color,
+        }),
+        MoveType::EnPassant => {
+            board[move_data.from.1][move_data.to.0].take();
+            piece_taken
+        }
+        MoveType::Castling(column) => {
+            let (rook_from, rook_to) = castling_rook_columns(move_data, column);
+            board[move_data.from.1][rook_to] = board[move_data.from.1][rook_from].take();
+            piece_taken
+        }
+        _ => piece_taken,
+    }
+}
+
+/// What `unmake_move` needs to take a move made with `make_move` back.
+#[derive(Copy, Clone, Debug)]
+pub struct Undo {
+    pub move_data: Move,
+    pub captured: Option<Piece>,
+    pub is_valid_castling: [[bool; 2]; 2],
+    pub recent_advancing_pawn: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+}
+
+fn castling_rook_columns(move_data: &Move, column: usize) -> (usize, usize) {
+    let to_column = if column == 7 {
+        move_data.to.0 - 1
+    } else {
+        move_data.to.0 + 1
+    };
+    (column, to_column)
+}
+
+fn is_rook_home_square(color: TeamColor, pos: (usize, usize)) -> bool {
+    pos.1 == color.home_row() && [0, 7].contains(&pos.0)
+}
+
+fn update_castling_data(data: &mut GameData, color: TeamColor, column: usize) {
+    let column_ptr = if column == 0 { 0 } else { 1 };
+    data.is_valid_castling[color.index()][column_ptr] = false;
+}
+
+/// Makes `move_data` for the side to move and hands the turn to the other
+/// side, updating the castling rights, the en passant pawn and both clocks.
+/// The legal moves and position history are left for the caller to refresh.
+pub fn make_move(board: &mut Board, data: &mut GameData, move_data: &Move) -> Undo {
+    let (from, to) = (move_data.from, move_data.to);
+    let piece_taken = board[from.1][from.0];
+    let captured = match move_data.variant {
+        MoveType::EnPassant => board[from.1][to.0],
+        _ => board[to.1][to.0],
+    };
+    let undo = Undo {
+        move_data: *move_data,
+        captured,
+        is_valid_castling: data.is_valid_castling,
+        recent_advancing_pawn: data.recent_advancing_pawn,
+        halfmove_clock: data.halfmove_clock,
+        fullmove_number: data.fullmove_number,
+        hash: data.hash,
+    };
+
+    // take out everything about to change from the key, then add it back
+    // once the move is made
+    let mut hash = data.hash
+        ^ zobrist::castling_key(data.is_valid_castling)
+        ^ zobrist::en_passant_key(board, data)
+        ^ zobrist::BLACK_TO_MOVE_KEY;
+    if let Some(piece) = piece_taken {
+        hash ^= zobrist::piece_key(piece, from);
+        let placed = match move_data.variant {
+            MoveType::Promotion(variant) => Piece {
+                variant,
+                color: piece.color,
+            },
+            _ => piece,
+        };
+        hash ^= zobrist::piece_key(placed, to);
+    }
+    if let Some(piece) = captured {
+        let captured_square = match move_data.variant {
+            MoveType::EnPassant => (to.0, from.1),
+            _ => to,
+        };
+        hash ^= zobrist::piece_key(piece, captured_square);
+    }
+    if let MoveType::Castling(column) = move_data.variant {
+        let (rook_from, rook_to) = castling_rook_columns(move_data, column);
+        if let Some(rook) = board[from.1][rook_from] {
+            hash ^= zobrist::piece_key(rook, (rook_from, from.1));
+            hash ^= zobrist::piece_key(rook, (rook_to, from.1));
+        }
+    }
+
+    let is_pawn_move = piece_taken.is_some_and(|piece| piece.variant == PieceVariant::Pawn);
+    if is_pawn_move || captured.is_some() {
+        data.halfmove_clock = 0;
+    } else {
+        data.halfmove_clock += 1;
+    }
+
+    if let Some(piece) = piece_taken {
+        match piece.variant {
+            PieceVariant::King => data.is_valid_castling[piece.color.index()] = [false; 2],
+            PieceVariant::Castle if is_rook_home_square(piece.color, from) => {
+                update_castling_data(data, piece.color, from.0)
+            }
+            _ => (),
+        }
+    }
+
+    // a rook captured on its home square takes its castling right with it
+    if let Some(piece) = captured {
+        if piece.variant == PieceVariant::Castle && is_rook_home_square(piece.color, to) {
+            update_castling_data(data, piece.color, to.0)
+        }
+    }
+
+    data.recent_advancing_pawn = match move_data.variant {
+        MoveType::AdvancePawn => Some(to),
+        _ => None,
+    };
+
+    if data.current_turn == TeamColor::Black {
+        data.fullmove_number += 1;
+    }
+    data.current_turn = data.current_turn.opposite();
+
+    play_move(board, move_data);
+
+    hash ^= zobrist::castling_key(data.is_valid_castling);
+    hash ^= zobrist::en_passant_key(board, data);
+    data.hash = hash;
+    undo
+}
+
+/// Takes back the move recorded in `undo`, restoring the board and the game
+/// data exactly as they were before `make_move`.
+pub fn unmake_move(board: &mut Board, data: &mut GameData, undo: &Undo) {
+    let move_data = undo.move_data;
+    let (from, to) = (move_data.from, move_data.to);
+
+    data.current_turn = data.current_turn.opposite();
+    data.is_valid_castling = undo.is_valid_castling;
+    data.recent_advancing_pawn = undo.recent_advancing_pawn;
+    data.halfmove_clock = undo.halfmove_clock;
+    data.fullmove_number = undo.fullmove_number;
+    data.hash = undo.hash;
+
+    let piece_moved = board[to.1][to.0].take();
+    board[from.1][from.0] = match move_data.variant {
+        MoveType::Promotion(_) => Some(Piece {
+            variant: PieceVariant::Pawn,
+            color: data.current_turn,
+        }),
+        _ => piece_moved,
+    };
+
+    match move_data.variant {
+        MoveType::EnPassant => board[from.1][to.0] = undo.captured,
+        MoveType::Castling(column) => {
+            let (rook_from, rook_to) = castling_rook_columns(&move_data, column);
+            board[from.1][rook_from] = board[from.1][rook_to].take();
+        }
+        _ => board[to.1][to.0] = undo.captured,
+    }
+}
+
+pub fn find_king(board: &Board, color: TeamColor) -> Option<(usize, usize)> {
+    (0..8)
+        .flat_map(|row| (0..8).map(move |column| (column, row)))
+        .find(|&(column, row)| {
+            get_square(board, column, row)
+                .is_some_and(|piece| piece.variant == PieceVariant::King && piece.color == color)
+        })
+}
+
+/// Tells whether any piece of `attacker` could capture on `square`.
+///
+/// Every piece type is generated from `square` as if it stood there for the
+/// other side; the square is attacked if one of those moves lands on an
+/// attacker piece of the same type.
+pub fn is_square_attacked(
+    board: &Board,
+    data: &GameData,
+    square: (usize, usize),
+    attacker: TeamColor,
+) -> bool {
+    use PieceVariant::*;
+
+    let defender = attacker.opposite();
+    let hits = |moves: HashSet<Move>, variants: &[PieceVariant]| {
+        moves.iter().any(|move_data| {
+            get_square(board, move_data.to.0, move_data.to.1)
+                .is_some_and(|piece| piece.color == attacker && variants.contains(&piece.variant))
+        })
+    };
+
+    hits(
+        generate_sliding_moves(board, defender, square, (0, 4)),
+        &[Queen, Castle],
+    ) || hits(
+        generate_sliding_moves(board, defender, square, (4, 8)),
+        &[Queen, Bishop],
+    ) || hits(generate_knight_moves(board, defender, square), &[Knight])
+        || hits(generate_king_moves(board, defender, square), &[King])
+        || hits(generate_pawn_moves(board, data, defender, square), &[Pawn])
+}
+
+pub fn is_in_check(board: &Board, data: &GameData, color: TeamColor) -> bool {
+    match find_king(board, color) {
+        Some(square) => is_square_attacked(board, data, square, color.opposite()),
+        None => false,
+    }
+}
+
+/// Tells whether neither side has enough material left to ever checkmate:
+/// bare kings, a single minor piece, or bishops all standing on one color.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    use PieceVariant::*;
+
+    let mut minor_pieces = vec![];
+    for (row, squares) in board.iter().enumerate() {
+        for (column, square) in squares.iter().enumerate() {
+            match square {
+                Some(piece) if piece.variant == King => (),
+                Some(piece) if [Bishop, Knight].contains(&piece.variant) => {
+                    minor_pieces.push((piece.variant, (column + row) % 2))
+                }
+                Some(_) => return false,
+                None => (),
+            }
+        }
+    }
+
+    match minor_pieces.as_slice() {
+        [] | [_] => true,
+        [(_, square_color), ..] => minor_pieces
+            .iter()
+            .all(|&(variant, color)| variant == Bishop && color == *square_color),
+    }
+}
+
+/// Generates every legal move of the side to move, leaving out the moves
+/// that would leave its own king attacked.
+pub fn generate_legal_moves(board: &Board, data: &GameData) -> HashSet<Move> {
+    let color = data.current_turn;
+
+    generate_pseudo_legal_moves(board, data)
+        .into_iter()
+        .filter(|move_data| {
+            let mut board = *board;
+            play_move(&mut board, move_data);
+            !is_in_check(&board, data, color)
+        })
+        .collect()
+}
+
+pub fn generate_moves(game: &Game) -> HashSet<Move> {
+    generate_legal_moves(&game.board, &game.cache.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+
+    fn is_insufficient(position: &str) -> bool {
+        let (board, _) = fen::parse(position).unwrap();
+        is_insufficient_material(&board)
+    }
+
+    #[test]
+    fn bare_kings_and_single_minor_pieces_cannot_mate() {
+        assert!(is_insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(is_insufficient("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1"));
+        assert!(is_insufficient("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // both bishops on dark squares
+        assert!(is_insufficient("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn other_material_can_still_mate() {
+        // bishops on opposite colors
+        assert!(!is_insufficient("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!is_insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert!(!is_insufficient("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"));
+    }
+}
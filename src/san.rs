@@ -0,0 +1,338 @@
+use crate::game::{self, Board, GameData, PieceVariant};
+use crate::produce::{self, Move, MoveType};
+
+use std::fmt;
+
+pub fn piece_letter(variant: PieceVariant) -> &'static str {
+    match variant {
+        PieceVariant::King => "K",
+        PieceVariant::Queen => "Q",
+        PieceVariant::Castle => "R",
+        PieceVariant::Bishop => "B",
+        PieceVariant::Knight => "N",
+        PieceVariant::Pawn => "",
+    }
+}
+
+/// The game data of the opponent right after `move_data`, as far as legal
+/// move generation for the check and checkmate suffix needs it.
+fn data_after_move(data: &GameData, move_data: &Move) -> GameData {
+    let mut next = game::initialize_data();
+    next.current_turn = data.current_turn.opposite();
+    next.is_valid_castling = [[false; 2]; 2];
+    if move_data.variant == MoveType::AdvancePawn {
+        next.recent_advancing_pawn = Some(move_data.to);
+    }
+    next
+}
+
+/// Writes `move_data` in Standard Algebraic Notation. `data` must hold the
+/// legal moves of the position on `board`, which are needed to tell apart
+/// pieces of the same kind that can reach the same square.
+pub fn render(board: &Board, data: &GameData, move_data: &Move) -> String {
+    let mut san = match move_data.variant {
+        MoveType::Castling(0) => "O-O-O".to_string(),
+        MoveType::Castling(_) => "O-O".to_string(),
+        _ => render_piece_move(board, data, move_data),
+    };
+
+    let mut after = *board;
+    produce::play_move(&mut after, move_data);
+    let next = data_after_move(data, move_data);
+    if produce::is_in_check(&after, &next, next.current_turn) {
+        if produce::generate_legal_moves(&after, &next).is_empty() {
+            san.push('#');
+        } else {
+            san.push('+');
+        }
+    }
+
+    san
+}
+
+fn render_piece_move(board: &Board, data: &GameData, move_data: &Move) -> String {
+    let (from, to) = (move_data.from, move_data.to);
+    let variant = match board[from.1][from.0] {
+        Some(piece) => piece.variant,
+        None => return String::new(),
+    };
+    let is_capture = board[to.1][to.0].is_some() || move_data.variant == MoveType::EnPassant;
+    let from_name = game::square_name(from);
+
+    let mut san = piece_letter(variant).to_string();
+    if variant == PieceVariant::Pawn {
+        if is_capture {
+            san.push_str(&from_name[..1]);
+        }
+    } else {
+        let rivals: Vec<(usize, usize)> = data
+            .available_moves
+            .iter()
+            .filter(|other| other.to == to && other.from != from)
+            .filter(|other| {
+                board[other.from.1][other.from.0].is_some_and(|piece| piece.variant == variant)
+            })
+            .map(|other| other.from)
+            .collect();
+
+        if !rivals.is_empty() {
+            if rivals.iter().all(|rival| rival.0 != from.0) {
+                san.push_str(&from_name[..1]);
+            } else if rivals.iter().all(|rival| rival.1 != from.1) {
+                san.push_str(&from_name[1..]);
+            } else {
+                san.push_str(&from_name);
+            }
+        }
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&game::square_name(to));
+
+    if let MoveType::Promotion(promoted) = move_data.variant {
+        san.push('=');
+        san.push_str(piece_letter(promoted));
+    }
+
+    san
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SanError {
+    Malformed(String),
+    Illegal(String),
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::Malformed(san) => write!(f, "'{}' is not a move in algebraic notation", san),
+            SanError::Illegal(san) => write!(f, "'{}' is not a legal move here", san),
+            SanError::Ambiguous(san) => write!(f, "'{}' matches more than one legal move", san),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn parse_piece_letter(letter: char) -> Option<PieceVariant> {
+    match letter {
+        'K' => Some(PieceVariant::King),
+        'Q' => Some(PieceVariant::Queen),
+        'R' => Some(PieceVariant::Castle),
+        'B' => Some(PieceVariant::Bishop),
+        'N' => Some(PieceVariant::Knight),
+        _ => None,
+    }
+}
+
+/// Resolves a move in Standard Algebraic Notation against the legal moves in
+/// `data`. Check, checkmate and annotation suffixes are ignored, and castling
+/// written with zeros or a promotion without '=' are accepted as well.
+pub fn parse(board: &Board, data: &GameData, san: &str) -> Result<Move, SanError> {
+    let malformed = || SanError::Malformed(san.to_string());
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let castling_column = match text {
+        "O-O" | "0-0" => Some(7),
+        "O-O-O" | "0-0-0" => Some(0),
+        _ => None,
+    };
+    if let Some(column) = castling_column {
+        return data
+            .available_moves
+            .iter()
+            .find(|move_data| move_data.variant == MoveType::Castling(column))
+            .copied()
+            .ok_or_else(|| SanError::Illegal(san.to_string()));
+    }
+
+    let mut chars: Vec<char> = text.chars().filter(|c| !['x', ':'].contains(c)).collect();
+
+    let promotion = match chars.last() {
+        Some(&letter) if letter.is_ascii_alphabetic() => {
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            Some(parse_piece_letter(letter.to_ascii_uppercase()).ok_or_else(malformed)?)
+        }
+        _ => None,
+    };
+
+    if chars.len() < 2 {
+        return Err(malformed());
+    }
+    let destination: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let to = game::parse_square(&destination).ok_or_else(malformed)?;
+
+    let variant = match chars.first().and_then(|&letter| parse_piece_letter(letter)) {
+        Some(variant) => {
+            chars.remove(0);
+            variant
+        }
+        None => PieceVariant::Pawn,
+    };
+
+    let (mut from_column, mut from_row) = (None, None);
+    for c in chars {
+        match c {
+            'a'..='h' if from_column.is_none() => from_column = Some(c as usize - 'a' as usize),
+            '1'..='8' if from_row.is_none() => from_row = Some(c as usize - '1' as usize),
+            _ => return Err(malformed()),
+        }
+    }
+
+    let candidates: Vec<&Move> = data
+        .available_moves
+        .iter()
+        .filter(|move_data| move_data.to == to)
+        .filter(|move_data| !matches!(move_data.variant, MoveType::Castling(_)))
+        .filter(|move_data| {
+            board[move_data.from.1][move_data.from.0].is_some_and(|piece| piece.variant == variant)
+        })
+        .filter(|move_data| from_column.is_none_or(|column| move_data.from.0 == column))
+        .filter(|move_data| from_row.is_none_or(|row| move_data.from.1 == row))
+        .filter(|move_data| match move_data.variant {
+            MoveType::Promotion(promoted) => promotion == Some(promoted),
+            _ => promotion.is_none(),
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [move_data] => Ok(**move_data),
+        [] => Err(SanError::Illegal(san.to_string())),
+        _ => Err(SanError::Ambiguous(san.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+
+    /// Parses `san` in the position `text`, checks that it is the move from
+    /// `from` to `to` and that rendering it gives back `expected`.
+    fn assert_round_trip(text: &str, san: &str, from: &str, to: &str, expected: &str) {
+        let (board, data) = fen::parse(text).unwrap();
+        let move_data = parse(&board, &data, san).unwrap();
+        assert_eq!(move_data.from, game::parse_square(from).unwrap(), "{}", san);
+        assert_eq!(move_data.to, game::parse_square(to).unwrap(), "{}", san);
+        assert_eq!(render(&board, &data, &move_data), expected);
+    }
+
+    #[test]
+    fn disambiguates_by_file_rank_or_square() {
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_round_trip(knights, "Nbd2", "b1", "d2", "Nbd2");
+        assert_round_trip(knights, "Nfd2", "f1", "d2", "Nfd2");
+        assert_round_trip(knights, "Na3", "b1", "a3", "Na3");
+
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_round_trip(rooks, "R1a3", "a1", "a3", "R1a3");
+        assert_round_trip(rooks, "R5a3", "a5", "a3", "R5a3");
+
+        let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_round_trip(queens, "Qa1b2", "a1", "b2", "Qa1b2");
+        assert_round_trip(queens, "Qa3b2", "a3", "b2", "Q3b2");
+        assert_round_trip(queens, "Qc1b2", "c1", "b2", "Qcb2");
+    }
+
+    #[test]
+    fn pinned_rivals_need_no_disambiguation() {
+        let pinned = "4k3/8/8/8/1b6/8/3N4/4K1N1 w - - 0 1";
+        assert_round_trip(pinned, "Nf3", "g1", "f3", "Nf3");
+        assert_round_trip(pinned, "Ngf3", "g1", "f3", "Nf3");
+
+        let (board, data) = fen::parse(pinned).unwrap();
+        assert_eq!(
+            parse(&board, &data, "Ndf3"),
+            Err(SanError::Illegal("Ndf3".to_string()))
+        );
+    }
+
+    #[test]
+    fn castling() {
+        let white = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_round_trip(white, "O-O", "e1", "g1", "O-O");
+        assert_round_trip(white, "0-0", "e1", "g1", "O-O");
+        assert_round_trip(white, "O-O-O", "e1", "c1", "O-O-O");
+        assert_round_trip(white, "0-0-0", "e1", "c1", "O-O-O");
+
+        let black = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        assert_round_trip(black, "O-O", "e8", "g8", "O-O");
+        assert_round_trip(black, "O-O-O", "e8", "c8", "O-O-O");
+
+        let (board, data) = fen::parse("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(
+            parse(&board, &data, "O-O"),
+            Err(SanError::Illegal("O-O".to_string()))
+        );
+    }
+
+    #[test]
+    fn en_passant() {
+        let text = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2";
+        assert_round_trip(text, "exd6", "e5", "d6", "exd6");
+
+        let (board, data) = fen::parse(text).unwrap();
+        let move_data = parse(&board, &data, "exd6").unwrap();
+        assert_eq!(move_data.variant, MoveType::EnPassant);
+    }
+
+    #[test]
+    fn promotion() {
+        let text = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        assert_round_trip(text, "e8=Q", "e7", "e8", "e8=Q");
+        assert_round_trip(text, "e8Q", "e7", "e8", "e8=Q");
+        assert_round_trip(text, "e8=N", "e7", "e8", "e8=N");
+
+        let (board, data) = fen::parse(text).unwrap();
+        let move_data = parse(&board, &data, "e8R").unwrap();
+        assert_eq!(move_data.variant, MoveType::Promotion(PieceVariant::Castle));
+        assert_eq!(
+            parse(&board, &data, "e8"),
+            Err(SanError::Illegal("e8".to_string()))
+        );
+    }
+
+    #[test]
+    fn check_and_checkmate_suffixes() {
+        assert_round_trip("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "Ra8", "a1", "a8", "Ra8+");
+        assert_round_trip("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "Ra8+", "a1", "a8", "Ra8+");
+        assert_round_trip(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "Ra8#",
+            "a1",
+            "a8",
+            "Ra8#",
+        );
+        assert_round_trip(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "Ra8!?",
+            "a1",
+            "a8",
+            "Ra8#",
+        );
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let (board, data) = fen::parse("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        let errors = [
+            ("Nd2", SanError::Ambiguous("Nd2".to_string())),
+            ("Nc4", SanError::Illegal("Nc4".to_string())),
+            ("Be2", SanError::Illegal("Be2".to_string())),
+            ("Nz9", SanError::Malformed("Nz9".to_string())),
+            ("e", SanError::Malformed("e".to_string())),
+            ("N1b1d2", SanError::Malformed("N1b1d2".to_string())),
+            ("d8=X", SanError::Malformed("d8=X".to_string())),
+        ];
+        for (san, error) in errors {
+            assert_eq!(parse(&board, &data, san), Err(error), "{}", san);
+        }
+    }
+}
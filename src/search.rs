@@ -0,0 +1,563 @@
+use crate::bitboard::{BitMove, MoveList, Position};
+use crate::clock::TimeBudget;
+use crate::evaluate;
+use crate::ordering::{self, MoveOrdering, MAX_PLY};
+use crate::parameters::Parameters;
+use crate::syzygy::{Tablebases, Wdl};
+use crate::transposition::{self, Bound, TranspositionTable};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// The score of delivering mate right away. Mates further away score less
+/// by one per ply, so the search prefers the quickest one.
+pub const MATE: i32 = 30_000;
+/// The score of a position the tablebases know to be won, less one per ply
+/// from the root. Above any evaluation, below any mate.
+pub const TABLEBASE_WIN: i32 = 20_000;
+const INFINITY: i32 = 32_000;
+
+/// How often, in nodes, the search looks at the clock and the stop flag.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+/// Half the width of the window around the previous score that each new
+/// iteration is first searched with.
+const ASPIRATION_WINDOW: i32 = 40;
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - 1000
+}
+
+pub struct SearchOptions {
+    /// The deepest iteration to search.
+    pub depth: u32,
+    pub time: Option<TimeBudget>,
+    /// Largest random offset added to every evaluation, in centipawns, to
+    /// make weaker levels misjudge positions.
+    pub evaluation_noise: i32,
+    /// Varies the noise from one search to the next.
+    pub seed: u64,
+    pub parameters: Arc<Parameters>,
+    pub tablebases: Option<Arc<Tablebases>>,
+    /// Set from another thread to abort the search as if its time were up.
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl SearchOptions {
+    pub fn depth(depth: u32) -> SearchOptions {
+        SearchOptions {
+            depth,
+            time: None,
+            evaluation_noise: 0,
+            seed: 0,
+            parameters: Arc::new(Parameters::default()),
+            tablebases: None,
+            stop: None,
+        }
+    }
+}
+
+pub struct SearchResult {
+    pub best_move: Option<BitMove>,
+    /// Centipawns from the point of view of the side to move.
+    pub score: i32,
+    /// The deepest iteration that was searched to the end.
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+struct Search<'a> {
+    table: &'a mut TranspositionTable,
+    parameters: &'a Parameters,
+    tablebases: Option<&'a Tablebases>,
+    ordering: MoveOrdering,
+    /// Hashes of the positions before the current one, oldest first.
+    history: Vec<u64>,
+    nodes: u64,
+    /// When the search has to stop, even in the middle of an iteration.
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    is_aborted: bool,
+    evaluation_noise: i32,
+    seed: u64,
+}
+
+impl Search<'_> {
+    fn check_clock(&mut self) {
+        if self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            self.is_aborted = self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+                || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed));
+        }
+    }
+
+    /// A pseudo-random offset that stays the same for a position throughout
+    /// one search, so the noise does not make the search inconsistent.
+    fn noise(&self, hash: u64) -> i32 {
+        if self.evaluation_noise == 0 {
+            return 0;
+        }
+        let mut mixed = hash ^ self.seed;
+        mixed = (mixed ^ (mixed >> 33)).wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+        mixed ^= mixed >> 33;
+        let range = 2 * self.evaluation_noise as u64 + 1;
+        (mixed % range) as i32 - self.evaluation_noise
+    }
+
+    /// Whether the position on top of the history is drawn by the fifty-move
+    /// rule or repeats an earlier one. A single repetition is enough: if it
+    /// was worth repeating once it will be worth repeating again.
+    fn is_draw(&self, position: &Position) -> bool {
+        if position.halfmove_clock >= 100 {
+            return true;
+        }
+        self.history
+            .iter()
+            .rev()
+            .take(position.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|hash| *hash == position.hash)
+    }
+
+    /// The tablebase score of a position right after a capture or pawn
+    /// move, where the fifty-move count the tables assume starts over.
+    /// Cursed wins and blessed losses count as draws.
+    fn probe_tablebases(&self, position: &Position, ply: i32) -> Option<i32> {
+        if ply == 0 || position.halfmove_clock != 0 {
+            return None;
+        }
+        let score = match self.tablebases?.probe_wdl(position)? {
+            Wdl::Win => TABLEBASE_WIN - ply,
+            Wdl::Loss => -TABLEBASE_WIN + ply,
+            _ => 0,
+        };
+        Some(score)
+    }
+
+    fn negamax(
+        &mut self,
+        position: &Position,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if ply > 0 && self.is_draw(position) {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(position, ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+        self.check_clock();
+        if self.is_aborted {
+            return 0;
+        }
+
+        let entry = self.table.probe(position.hash);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            let score = transposition::score_from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => (),
+            }
+        }
+        if let Some(score) = self.probe_tablebases(position, ply) {
+            return score;
+        }
+
+        let mut moves = position.generate_legal_moves();
+        if moves.is_empty() {
+            return if position.is_in_check(position.side_to_move) {
+                -MATE + ply
+            } else {
+                0
+            };
+        }
+
+        let table_move = entry.and_then(|entry| entry.best_move);
+        self.ordering
+            .order(position, moves.as_mut_slice(), ply as usize, table_move);
+
+        self.history.push(position.hash);
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for move_data in moves.iter() {
+            let next = position.make_move(*move_data);
+            let score = -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha);
+            if score > best_score {
+                best_score = score;
+                best_move = Some(*move_data);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                self.ordering
+                    .record_cutoff(position.side_to_move, *move_data, ply as usize, depth);
+                break;
+            }
+        }
+        self.history.pop();
+
+        if !self.is_aborted {
+            self.store(
+                position,
+                depth,
+                ply,
+                best_score,
+                best_move,
+                original_alpha,
+                beta,
+            );
+        }
+        best_score
+    }
+
+    /// Resolves the captures left at the end of the main search so that the
+    /// evaluation is never taken in the middle of an exchange. The side to
+    /// move may always stand pat, unless it is in check and has to get out.
+    fn quiescence(&mut self, position: &Position, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.check_clock();
+        if self.is_aborted {
+            return 0;
+        }
+
+        let is_in_check = position.is_in_check(position.side_to_move);
+        let evaluation = evaluate::evaluate(position, self.parameters) + self.noise(position.hash);
+        if ply as usize >= MAX_PLY {
+            return evaluation;
+        }
+
+        let mut best_score = -INFINITY;
+        let mut moves = if is_in_check {
+            position.generate_legal_moves()
+        } else {
+            best_score = evaluation;
+            if evaluation >= beta {
+                return evaluation;
+            }
+            alpha = alpha.max(evaluation);
+            position.generate_legal_captures()
+        };
+        if is_in_check && moves.is_empty() {
+            return -MATE + ply;
+        }
+        ordering::order_captures(position, moves.as_mut_slice());
+
+        for move_data in moves.iter() {
+            // captures that lose material cannot raise a standing pat score
+            if !is_in_check && ordering::static_exchange(position, *move_data) < 0 {
+                continue;
+            }
+
+            let next = position.make_move(*move_data);
+            let score = -self.quiescence(&next, ply + 1, -beta, -alpha);
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn store(
+        &mut self,
+        position: &Position,
+        depth: u32,
+        ply: i32,
+        score: i32,
+        best_move: Option<BitMove>,
+        alpha: i32,
+        beta: i32,
+    ) {
+        let bound = if score <= alpha {
+            Bound::Upper
+        } else if score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        // a move that failed low is no better than the others
+        let best_move = best_move.filter(|_| bound != Bound::Upper);
+        let score = transposition::score_to_table(score, ply);
+        self.table
+            .store(position.hash, depth, bound, score, best_move);
+    }
+
+    /// Searches every root move in `moves`, which has the best move of the
+    /// previous iteration up front, and returns the best score with its move.
+    fn search_root(
+        &mut self,
+        position: &Position,
+        moves: &[BitMove],
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, Option<BitMove>) {
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for move_data in moves {
+            let next = position.make_move(*move_data);
+            let score = -self.negamax(&next, depth - 1, 1, -beta, -alpha);
+            if self.is_aborted {
+                return (best_score, best_move);
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(*move_data);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        self.store(
+            position,
+            depth,
+            0,
+            best_score,
+            best_move,
+            original_alpha,
+            beta,
+        );
+        (best_score, best_move)
+    }
+}
+
+/// Searches one ply deeper at a time up to `options.depth`, starting every
+/// iteration with a narrow window around the score of the one before and
+/// widening it when the score falls outside. When the time is up or
+/// `options.stop` is set the search stops, even in the middle of an
+/// iteration, and answers with the result of the last one it finished.
+/// `history` holds the hashes of the positions played before this one so
+/// repetitions count as draws. `table` may hold results of earlier
+/// searches, from this game or another.
+pub fn search(
+    position: &Position,
+    history: &[u64],
+    options: &SearchOptions,
+    table: &mut TranspositionTable,
+) -> SearchResult {
+    let started = Instant::now();
+    table.new_search();
+    let mut search = Search {
+        table,
+        parameters: &options.parameters,
+        tablebases: options.tablebases.as_deref(),
+        ordering: MoveOrdering::new(),
+        history: history.to_vec(),
+        nodes: 0,
+        deadline: options.time.map(|time| started + time.hard),
+        stop: options.stop.as_deref(),
+        is_aborted: false,
+        evaluation_noise: options.evaluation_noise,
+        seed: options.seed,
+    };
+    search.history.push(position.hash);
+
+    let mut moves: MoveList = position.generate_legal_moves();
+    // where the tablebases know the position, only the moves that keep its
+    // result are searched
+    let tablebase_moves = options
+        .tablebases
+        .as_ref()
+        .and_then(|tablebases| tablebases.best_moves(position));
+    if let Some(best_moves) = tablebase_moves.filter(|best_moves| !best_moves.is_empty()) {
+        moves = MoveList::new();
+        for move_data in best_moves {
+            moves.push(move_data);
+        }
+    }
+    let mut result = SearchResult {
+        best_move: moves.first().copied(),
+        score: 0,
+        depth: 0,
+        nodes: 0,
+    };
+
+    'deepening: for depth in 1..=options.depth.max(1) {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if depth > 1 && !is_mate_score(result.score) {
+            (result.score - delta, result.score + delta)
+        } else {
+            (-INFINITY, INFINITY)
+        };
+
+        loop {
+            let (score, best_move) = search.search_root(position, &moves, depth, alpha, beta);
+            if search.is_aborted {
+                break 'deepening;
+            }
+
+            if score <= alpha && alpha > -INFINITY {
+                alpha = (alpha - delta).max(-INFINITY);
+            } else if score >= beta && beta < INFINITY {
+                beta = (beta + delta).min(INFINITY);
+            } else {
+                result.best_move = best_move;
+                result.score = score;
+                result.depth = depth;
+                break;
+            }
+            delta *= 2;
+        }
+
+        // the next iteration starts with the best move found so far
+        if let Some(index) = moves
+            .iter()
+            .position(|move_data| Some(*move_data) == result.best_move)
+        {
+            moves.as_mut_slice()[..=index].rotate_right(1);
+        }
+
+        let is_soft_limit_reached = options
+            .time
+            .is_some_and(|time| started.elapsed() >= time.soft);
+        if moves.len() <= 1 || is_soft_limit_reached {
+            break;
+        }
+    }
+
+    result.nodes = search.nodes;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+    use crate::uci;
+
+    use std::time::Duration;
+
+    fn best_move(position: &str, depth: u32) -> (String, i32) {
+        let result = search(
+            &Position::from_fen(position).unwrap(),
+            &[],
+            &SearchOptions::depth(depth),
+            &mut TranspositionTable::new(1),
+        );
+        let best_move = result.best_move.unwrap().to_move();
+        (uci::render(&best_move), result.score)
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let (best_move, score) = best_move("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 2);
+        assert_eq!(best_move, "d1d8");
+        assert_eq!(score, MATE - 1);
+    }
+
+    #[test]
+    fn takes_a_free_piece() {
+        let (best_move, _) = best_move(
+            "rnb1kbnr/pppp1ppp/8/4p1q1/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 3",
+            3,
+        );
+        assert_eq!(best_move, "c1g5");
+    }
+
+    #[test]
+    fn looks_past_the_horizon_for_recaptures() {
+        // a single ply deep, only the quiescence search sees the pawn take back
+        let (best_move, score) = best_move("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", 1);
+        assert_ne!(best_move, "e1e5");
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        let result = search(
+            &Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap(),
+            &[],
+            &SearchOptions::depth(3),
+            &mut TranspositionTable::new(1),
+        );
+        assert!(result.best_move.is_none());
+    }
+
+    #[test]
+    fn answers_in_time_with_a_finished_iteration() {
+        let position = Position::from_fen(fen::STARTING_POSITION).unwrap();
+        let options = SearchOptions {
+            time: Some(TimeBudget::fixed(Duration::from_millis(100))),
+            ..SearchOptions::depth(20)
+        };
+
+        let started = Instant::now();
+        let mut table = TranspositionTable::new(1);
+        let result = search(&position, &[], &options, &mut table);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(result.best_move.is_some());
+        assert!((1..20).contains(&result.depth));
+    }
+
+    #[test]
+    fn stops_when_asked_to() {
+        let position = Position::from_fen(fen::STARTING_POSITION).unwrap();
+        let options = SearchOptions {
+            stop: Some(Arc::new(AtomicBool::new(true))),
+            ..SearchOptions::depth(20)
+        };
+
+        let started = Instant::now();
+        let result = search(&position, &[], &options, &mut TranspositionTable::new(1));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(result.best_move.is_some());
+        assert!(result.depth < 20);
+    }
+
+    #[test]
+    fn aspiration_windows_do_not_change_the_result() {
+        // a full window search of the same depth for comparison
+        let position = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let parameters = Parameters::default();
+        let mut plain = Search {
+            table: &mut table,
+            parameters: &parameters,
+            tablebases: None,
+            ordering: MoveOrdering::new(),
+            history: vec![position.hash],
+            nodes: 0,
+            deadline: None,
+            stop: None,
+            is_aborted: false,
+            evaluation_noise: 0,
+            seed: 0,
+        };
+        let moves = position.generate_legal_moves();
+        let (score, _) = plain.search_root(&position, &moves, 3, -INFINITY, INFINITY);
+
+        let mut table = TranspositionTable::new(1);
+        let options = SearchOptions::depth(3);
+        assert_eq!(search(&position, &[], &options, &mut table).score, score);
+    }
+
+    #[test]
+    fn table_saves_work_on_a_second_search() {
+        let position =
+            Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mut table = TranspositionTable::new(4);
+        let options = SearchOptions::depth(4);
+
+        let first = search(&position, &[], &options, &mut table);
+        let second = search(&position, &[], &options, &mut table);
+        assert!(second.nodes < first.nodes / 2);
+        assert_eq!(second.best_move, first.best_move);
+    }
+}
@@ -0,0 +1,1922 @@
+use crate::bitboard::{self, BitMove, Position, VARIANTS};
+use crate::game::{Piece, PieceVariant, TeamColor};
+use crate::produce::MoveType;
+use crate::Error;
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// The most pieces, kings included, that Syzygy tables exist for.
+const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// flags in the first byte of a file
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// flags of every sub-table
+const STORES_BLACK_TO_MOVE: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// The result of a position under perfect play, for the side to move. A
+/// cursed win is a win the fifty-move rule turns into a draw, a blessed loss
+/// the loss it saves.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    /// The same result seen from the other side.
+    pub fn opposite(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+
+    /// The DTZ of a position whose best move is a capture or pawn move, which
+    /// the DTZ tables do not store.
+    fn zeroing_dtz(self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+}
+
+/// What the tablebases know about a position, as shown in the analysis view.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Verdict {
+    pub side_to_move: TeamColor,
+    pub wdl: Wdl,
+    /// See `Tablebases::probe_dtz`. Missing when there is no DTZ file.
+    pub dtz: Option<i32>,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let winner = match self.wdl {
+            Wdl::Win => self.side_to_move,
+            Wdl::Loss => self.side_to_move.opposite(),
+            Wdl::Draw => return write!(f, "Draw"),
+            Wdl::CursedWin | Wdl::BlessedLoss => return write!(f, "Draw by the fifty-move rule"),
+        };
+        let winner = match winner {
+            TeamColor::White => "White",
+            TeamColor::Black => "Black",
+        };
+        // the tables count moves to the next capture or pawn move, not to mate
+        match self.dtz {
+            Some(dtz) => write!(f, "{} wins in {}", winner, (dtz.abs() + 1) / 2),
+            None => write!(f, "{} wins", winner),
+        }
+    }
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+/// The tables the position encoding counts with, the same for every file.
+struct Indexing {
+    /// Squares a2 to h7 numbered so the pawn most toward the edges, and the
+    /// lowest among those, comes last.
+    map_pawns: [usize; 64],
+    /// The squares below the a1-h8 diagonal numbered 0 to 27.
+    map_b1h1h7: [usize; 64],
+    /// The squares of the a1-d1-d4 triangle numbered 0 to 9, the diagonal
+    /// last.
+    map_a1d1d4: [usize; 64],
+    /// The 462 ways to place two kings with the first one in the a1-d1-d4
+    /// triangle, by that king's `map_a1d1d4` number and the other's square.
+    map_kk: [[usize; 64]; 10],
+    /// `binomial[k][n]` ways to pick `k` of `n` squares.
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Where the positions with the leading pawn on a square start, by the
+    /// number of leading pawns.
+    lead_pawn_index: [[u64; 64]; 6],
+    /// The positions of the leading pawns per file, by their number.
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indexing {
+    fn new() -> Indexing {
+        let mut indexing = Indexing {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            lead_pawn_index: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let below_diagonal = (0..64).filter(|square| off_diagonal(*square) < 0);
+        for (code, square) in below_diagonal.enumerate() {
+            indexing.map_b1h1h7[square] = code;
+        }
+
+        let triangle: Vec<usize> = (0..=27).filter(|square| square % 8 <= 3).collect();
+        let below = triangle.iter().filter(|square| off_diagonal(**square) < 0);
+        let on = triangle.iter().filter(|square| off_diagonal(**square) == 0);
+        for (code, square) in below.chain(on).enumerate() {
+            indexing.map_a1d1d4[*square] = code;
+        }
+
+        // with the first king on the diagonal the other one stays on or
+        // below it, and both on the diagonal come last
+        let mut both_on_diagonal = vec![];
+        let mut code = 0;
+        for index in 0..10 {
+            let first = triangle
+                .iter()
+                .find(|square| indexing.map_a1d1d4[**square] == index)
+                .copied()
+                .unwrap_or(0);
+            for second in 0..64 {
+                let is_touching = (bitboard::king_attacks(first) | 1 << first) & 1 << second != 0;
+                if is_touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                    continue;
+                }
+                if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                    both_on_diagonal.push((index, second));
+                } else {
+                    indexing.map_kk[index][second] = code;
+                    code += 1;
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            indexing.map_kk[index][second] = code;
+            code += 1;
+        }
+
+        indexing.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                let with = if k > 0 {
+                    indexing.binomial[k - 1][n - 1]
+                } else {
+                    0
+                };
+                let without = if k < n {
+                    indexing.binomial[k][n - 1]
+                } else {
+                    0
+                };
+                indexing.binomial[k][n] = with + without;
+            }
+        }
+
+        let mut available = 48;
+        for count in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..=6 {
+                    let square = rank * 8 + file;
+                    if count == 1 {
+                        available -= 1;
+                        indexing.map_pawns[square] = available;
+                        available -= 1;
+                        indexing.map_pawns[square ^ 7] = available;
+                    }
+                    indexing.lead_pawn_index[count][square] = index;
+                    index += indexing.binomial[count - 1][indexing.map_pawns[square]];
+                }
+                indexing.lead_pawns_size[count][file] = index;
+            }
+        }
+        indexing
+    }
+}
+
+fn indexing() -> &'static Indexing {
+    static INDEXING: OnceLock<Indexing> = OnceLock::new();
+    INDEXING.get_or_init(Indexing::new)
+}
+
+fn u16_le(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_be(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+/// One compressed table of a file: the values of every position for one
+/// side to move and, with pawns, one file of the leading pawn. Offsets point
+/// into the bytes of the file.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Also the value of every position when there is only one.
+    min_symbol_length: u8,
+    block_size: usize,
+    /// Every `span` values there is an entry in the sparse index.
+    span: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    /// How many values, minus one, each block holds.
+    block_lengths: usize,
+    block_lengths_size: usize,
+    block_count: usize,
+    /// The lowest Huffman symbol of each length.
+    lowest_symbols: usize,
+    /// The two symbols every symbol stands for.
+    tree: usize,
+    data: usize,
+    /// The lowest symbol of each length, padded to 64 bits.
+    base64: Vec<u64>,
+    /// How many values, minus one, each symbol stands for.
+    symbol_lengths: Vec<u8>,
+    /// The pieces in the order they are encoded in, see `piece_code`.
+    pieces: [u8; MAX_PIECES],
+    /// The pieces are encoded in groups of the same kind, except for the
+    /// leading group; `group_length` ends with a zero.
+    group_index: [u64; MAX_PIECES + 1],
+    group_length: [usize; MAX_PIECES + 1],
+    /// Where the DTZ values for wins, losses, cursed wins and blessed losses
+    /// are mapped, relative to the start of the maps.
+    map_index: [u16; 4],
+}
+
+impl PairsData {
+    fn set_groups(&mut self, table: &Table, order: [u8; 2], file: usize) {
+        let indexing = indexing();
+        let mut first_length: i32 = if table.has_pawns {
+            0
+        } else if table.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut n = 0;
+        self.group_length[0] = 1;
+        for i in 1..table.piece_count {
+            first_length -= 1;
+            if first_length > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_length[n] += 1;
+            } else {
+                n += 1;
+                self.group_length[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_length[n] = 0;
+
+        // the groups are encoded in the order the file gives, which is not
+        // necessarily the order of the pieces
+        let both_have_pawns = table.has_pawns && table.pawn_counts[1] > 0;
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_length[0];
+        if both_have_pawns {
+            free_squares -= self.group_length[1];
+        }
+        let mut index = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                self.group_index[0] = index;
+                index *= if table.has_pawns {
+                    indexing.lead_pawns_size[self.group_length[0]][file]
+                } else if table.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                self.group_index[1] = index;
+                index *= indexing.binomial[self.group_length[1]][48 - self.group_length[0]];
+            } else {
+                self.group_index[next] = index;
+                index *= indexing.binomial[self.group_length[next]][free_squares];
+                free_squares -= self.group_length[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_index[n] = index;
+    }
+
+    /// Reads the layout of the compressed values starting at `offset` and
+    /// returns where the next sub-table starts.
+    fn set_sizes(&mut self, bytes: &[u8], offset: usize) -> Option<usize> {
+        self.flags = *bytes.get(offset)?;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_symbol_length = *bytes.get(offset + 1)?;
+            return Some(offset + 2);
+        }
+
+        let groups = self.group_length.iter().position(|length| *length == 0)?;
+        let size = self.group_index[groups];
+        self.block_size = 1 << bytes.get(offset + 1)?;
+        self.span = 1 << bytes.get(offset + 2)?;
+        self.sparse_index_size = size.div_ceil(self.span as u64) as usize;
+        let padding = *bytes.get(offset + 3)? as usize;
+        self.block_count = u32_le(bytes, offset + 4)? as usize;
+        // padded so the sparse index never points past the end
+        self.block_lengths_size = self.block_count + padding;
+        let max_symbol_length = *bytes.get(offset + 8)?;
+        self.min_symbol_length = *bytes.get(offset + 9)?;
+        self.lowest_symbols = offset + 10;
+
+        // canonical Huffman codes: longer symbols have lower values, so
+        // the length of a symbol follows from the first base it is not
+        // below
+        let lengths = max_symbol_length.checked_sub(self.min_symbol_length)? as usize + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = u16_le(bytes, self.lowest_symbols + 2 * i)? as u64;
+            let next_lowest = u16_le(bytes, self.lowest_symbols + 2 * (i + 1))? as u64;
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            let shift = 64usize.checked_sub(i + self.min_symbol_length as usize)?;
+            *base = base.checked_shl(shift as u32).unwrap_or(0);
+        }
+
+        let offset = self.lowest_symbols + 2 * lengths;
+        let symbol_count = u16_le(bytes, offset)? as usize;
+        self.tree = offset + 2;
+        self.symbol_lengths = vec![0; symbol_count];
+        let mut visited = vec![false; symbol_count];
+        for symbol in 0..symbol_count {
+            if !visited[symbol] {
+                self.symbol_lengths[symbol] = self.symbol_length(bytes, symbol, &mut visited)?;
+            }
+        }
+        Some(self.tree + 3 * symbol_count + (symbol_count & 1))
+    }
+
+    /// The two symbols `symbol` stands for, in order.
+    fn children(&self, bytes: &[u8], symbol: usize) -> Option<(usize, usize)> {
+        let entry = bytes.get(self.tree + 3 * symbol..self.tree + 3 * symbol + 3)?;
+        let left = ((entry[1] as usize & 0xF) << 8) | entry[0] as usize;
+        let right = ((entry[2] as usize) << 4) | (entry[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    /// Expands `symbol` down to the values it stands for, filling in the
+    /// lengths of the symbols on the way.
+    fn symbol_length(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let (left, right) = self.children(bytes, symbol)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symbol_lengths[child] = self.symbol_length(bytes, child, visited)?;
+            }
+        }
+        let length = self.symbol_lengths[left] as u32 + self.symbol_lengths[right] as u32 + 1;
+        Some(length as u8)
+    }
+
+    /// The value stored for the position with `index`, reading only the
+    /// parts of the file that lead to it.
+    fn decompress(&self, data: &TableData, index: u64) -> Option<i32> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Some(self.min_symbol_length as i32);
+        }
+
+        // the sparse index points into the middle of every span, walk the
+        // blocks from there to the one holding `index`
+        let span = self.span as u64;
+        let entry = data.read(self.sparse_index + 6 * (index / span) as usize, 6)?;
+        let mut block = u32_le(&entry, 0)? as usize;
+        let mut offset = u16_le(&entry, 4)? as i64;
+        offset += (index % span) as i64 - (span / 2) as i64;
+        let block_length = |block: usize| {
+            let length = data.read(self.block_lengths + 2 * block, 2)?;
+            Some(u16_le(&length, 0)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // read symbols until the one that covers `offset`
+        // the buffer is refilled up to eight bytes past what it has used
+        let min_length = self.min_symbol_length as usize;
+        let bytes = data.read(self.data + block * self.block_size, self.block_size + 8)?;
+        let mut buffer = u64_be(&bytes, 0)?;
+        let mut pointer = 8;
+        let mut buffer_size: usize = 64;
+        let mut symbol;
+        loop {
+            let mut length = 0;
+            while buffer < *self.base64.get(length)? {
+                length += 1;
+            }
+            let shift = 64usize.checked_sub(length + min_length)? as u32;
+            symbol = ((buffer - self.base64[length])
+                .checked_shr(shift)
+                .unwrap_or(0) as u16)
+                .wrapping_add(u16_le(&data.header, self.lowest_symbols + 2 * length)?)
+                as usize;
+            let count = *self.symbol_lengths.get(symbol)? as i64 + 1;
+            if offset < count {
+                break;
+            }
+            offset -= count;
+            length += min_length;
+            buffer = buffer.checked_shl(length as u32).unwrap_or(0);
+            buffer_size = buffer_size.checked_sub(length)?;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (u32_be(&bytes, pointer)? as u64) << (64 - buffer_size);
+                pointer += 4;
+            }
+        }
+
+        // then down the pairs the symbol stands for to a single value
+        while *self.symbol_lengths.get(symbol)? != 0 {
+            let (left, right) = self.children(&data.header, symbol)?;
+            let count = *self.symbol_lengths.get(left)? as i64 + 1;
+            if offset < count {
+                symbol = left;
+            } else {
+                offset -= count;
+                symbol = right;
+            }
+        }
+        Some(self.children(&data.header, symbol)?.0 as i32)
+    }
+}
+
+/// Fills `bytes` from `file`, starting at `offset`.
+fn read_exact_at(file: &mut File, bytes: &mut [u8], offset: usize) -> io::Result<()> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.read_exact(bytes)
+}
+
+/// One .rtbw or .rtbz file. Files run to gigabytes, so only the header is
+/// kept in memory and the values are read from the file as they are probed.
+struct TableData {
+    /// Shared by the threads probing the table, which each seek before
+    /// they read.
+    file: Mutex<File>,
+    length: usize,
+    /// Everything up to the sparse indexes: the layout and Huffman trees of
+    /// the sub-tables and the DTZ maps.
+    header: Vec<u8>,
+    /// By the file of the leading pawn, then by the side to move.
+    pairs: Vec<Vec<PairsData>>,
+    /// Where the DTZ value maps start.
+    map: usize,
+}
+
+impl TableData {
+    fn load(path: &Path, kind: Kind, table: &Table) -> Option<TableData> {
+        let mut file = File::open(path).ok()?;
+        let length = file.metadata().ok()?.len() as usize;
+
+        // the header takes a small part of the file; start with a little
+        // and read more while the layout runs past it
+        let mut header_length = 4096;
+        let (header, mut pairs, map, mut offset) = loop {
+            let mut header = vec![0; header_length.min(length)];
+            read_exact_at(&mut file, &mut header, 0).ok()?;
+            match read_layout(&header, kind, table) {
+                Some((pairs, map, offset)) if offset <= header.len() => {
+                    header.truncate(offset);
+                    break (header, pairs, map, offset);
+                }
+                _ if header_length < length => header_length *= 2,
+                _ => return None,
+            }
+        };
+
+        for side_pairs in pairs.iter_mut().flatten() {
+            side_pairs.sparse_index = offset;
+            offset += 6 * side_pairs.sparse_index_size;
+        }
+        for side_pairs in pairs.iter_mut().flatten() {
+            side_pairs.block_lengths = offset;
+            offset += 2 * side_pairs.block_lengths_size;
+        }
+        for side_pairs in pairs.iter_mut().flatten() {
+            offset = (offset + 0x3F) & !0x3F;
+            side_pairs.data = offset;
+            offset += side_pairs.block_count * side_pairs.block_size;
+        }
+
+        (offset <= length).then_some(TableData {
+            file: Mutex::new(file),
+            length,
+            header,
+            pairs,
+            map,
+        })
+    }
+
+    /// Up to `length` bytes from `offset`, fewer at the end of the file.
+    fn read(&self, offset: usize, length: usize) -> Option<Vec<u8>> {
+        let mut bytes = vec![0; length.min(self.length.checked_sub(offset)?)];
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        read_exact_at(&mut file, &mut bytes, offset).ok()?;
+        Some(bytes)
+    }
+
+    fn pairs(&self, side: usize, file: usize) -> &PairsData {
+        let file_pairs = &self.pairs[file.min(self.pairs.len() - 1)];
+        &file_pairs[side % file_pairs.len()]
+    }
+
+    /// Turns a stored DTZ value back into plies, see `set_dtz_maps`.
+    fn map_dtz(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        let pairs = self.pairs(0, file);
+        let mut value = value;
+        if pairs.flags & MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            };
+            let index = pairs.map_index[map] as usize + value as usize;
+            value = if pairs.flags & WIDE != 0 {
+                u16_le(&self.header, self.map + 2 * index)? as i32
+            } else {
+                *self.header.get(self.map + index)? as i32
+            };
+        }
+
+        let is_in_moves = match wdl {
+            Wdl::Win => pairs.flags & WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if is_in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+/// Reads the layout of every sub-table from the start of a file. Returns it
+/// with where the DTZ maps start and where the sparse indexes do.
+fn read_layout(
+    bytes: &[u8],
+    kind: Kind,
+    table: &Table,
+) -> Option<(Vec<Vec<PairsData>>, usize, usize)> {
+    let magic = match kind {
+        Kind::Wdl => WDL_MAGIC,
+        Kind::Dtz => DTZ_MAGIC,
+    };
+    if bytes.get(..4)? != magic {
+        return None;
+    }
+    let flags = *bytes.get(4)?;
+    if (flags & HAS_PAWNS != 0) != table.has_pawns || (flags & SPLIT != 0) == table.is_symmetric {
+        return None;
+    }
+
+    // DTZ files only store one side to move, and so do WDL files when
+    // both sides have the same pieces
+    let sides = if kind == Kind::Wdl && !table.is_symmetric {
+        2
+    } else {
+        1
+    };
+    let files = if table.has_pawns { 4 } else { 1 };
+    let both_have_pawns = table.has_pawns && table.pawn_counts[1] > 0;
+    let mut pairs = vec![vec![PairsData::default(); sides]; files];
+    let mut offset = 5;
+    for (file, file_pairs) in pairs.iter_mut().enumerate() {
+        let first = *bytes.get(offset)?;
+        let second = if both_have_pawns {
+            *bytes.get(offset + 1)?
+        } else {
+            0xFF
+        };
+        let orders = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+        offset += if both_have_pawns { 2 } else { 1 };
+
+        for k in 0..table.piece_count {
+            let piece = *bytes.get(offset + k)?;
+            for (side, side_pairs) in file_pairs.iter_mut().enumerate() {
+                side_pairs.pieces[k] = if side == 0 { piece & 0xF } else { piece >> 4 };
+            }
+        }
+        offset += table.piece_count;
+        for (side, side_pairs) in file_pairs.iter_mut().enumerate() {
+            side_pairs.set_groups(table, orders[side], file);
+        }
+    }
+    offset += offset & 1;
+
+    for side_pairs in pairs.iter_mut().flatten() {
+        offset = side_pairs.set_sizes(bytes, offset)?;
+    }
+    let map = offset;
+    if kind == Kind::Dtz {
+        offset = set_dtz_maps(&mut pairs, bytes, offset)?;
+    }
+    Some((pairs, map, offset))
+}
+
+/// DTZ values are stored as their rank by frequency, per result; the maps
+/// turn the ranks back into values.
+fn set_dtz_maps(pairs: &mut [Vec<PairsData>], bytes: &[u8], offset: usize) -> Option<usize> {
+    let map = offset;
+    let mut offset = offset;
+    for file_pairs in pairs.iter_mut() {
+        let pairs = &mut file_pairs[0];
+        if pairs.flags & MAPPED == 0 {
+            continue;
+        }
+        if pairs.flags & WIDE != 0 {
+            offset += offset & 1;
+            for map_index in pairs.map_index.iter_mut() {
+                *map_index = ((offset - map) / 2 + 1) as u16;
+                offset += 2 * u16_le(bytes, offset)? as usize + 2;
+            }
+        } else {
+            for map_index in pairs.map_index.iter_mut() {
+                *map_index = (offset - map + 1) as u16;
+                offset += *bytes.get(offset)? as usize + 1;
+            }
+        }
+    }
+    Some(offset + (offset & 1))
+}
+
+/// The material of one file pair, e.g. KRPvKR, with the side named first as
+/// White. The files themselves are read when first probed.
+struct Table {
+    piece_count: usize,
+    has_pawns: bool,
+    /// Some side has a piece other than the king that it has only one of.
+    has_unique_pieces: bool,
+    /// Pawns of the side the encoding starts with, then of the other side.
+    pawn_counts: [usize; 2],
+    /// Both sides have the same pieces, as in KRvKR.
+    is_symmetric: bool,
+    wdl_path: PathBuf,
+    dtz_path: PathBuf,
+    wdl: OnceLock<Option<TableData>>,
+    dtz: OnceLock<Option<TableData>>,
+}
+
+/// Counts the pieces of a side named like "KRP", in the order of `VARIANTS`.
+fn parse_material(side: &str) -> Option<[usize; 6]> {
+    let mut counts = [0; 6];
+    for letter in side.chars() {
+        counts["KQRBNP".find(letter)?] += 1;
+    }
+    (counts[0] == 1).then_some(counts)
+}
+
+impl Table {
+    fn new(name: &str, wdl_path: &Path) -> Option<Table> {
+        let (first, second) = name.split_once('v')?;
+        let (first, second) = (parse_material(first)?, parse_material(second)?);
+        let piece_count = first.iter().chain(second.iter()).sum();
+        if !(3..=MAX_PIECES).contains(&piece_count) {
+            return None;
+        }
+
+        // the encoding starts with the side with fewer pawns, if it has any
+        let (first_pawns, second_pawns) = (first[5], second[5]);
+        let is_first_leading =
+            second_pawns == 0 || (first_pawns > 0 && second_pawns >= first_pawns);
+        let pawn_counts = if is_first_leading {
+            [first_pawns, second_pawns]
+        } else {
+            [second_pawns, first_pawns]
+        };
+
+        Some(Table {
+            piece_count,
+            has_pawns: first_pawns + second_pawns > 0,
+            has_unique_pieces: first[1..].contains(&1) || second[1..].contains(&1),
+            pawn_counts,
+            is_symmetric: first == second,
+            wdl_path: wdl_path.to_path_buf(),
+            dtz_path: wdl_path.with_extension("rtbz"),
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new(),
+        })
+    }
+
+    /// The contents of the file of `kind`, read on first use. Missing or
+    /// damaged files make every probe of them fail.
+    fn data(&self, kind: Kind) -> Option<&TableData> {
+        let (data, path) = match kind {
+            Kind::Wdl => (&self.wdl, &self.wdl_path),
+            Kind::Dtz => (&self.dtz, &self.dtz_path),
+        };
+        data.get_or_init(|| TableData::load(path, kind, self))
+            .as_ref()
+    }
+}
+
+/// The code the tables give a piece: 1 to 6 from pawn to king, plus 8 for
+/// Black.
+fn piece_code(piece: Piece) -> u8 {
+    let kind = match piece.variant {
+        PieceVariant::Pawn => 1,
+        PieceVariant::Knight => 2,
+        PieceVariant::Bishop => 3,
+        PieceVariant::Castle => 4,
+        PieceVariant::Queen => 5,
+        PieceVariant::King => 6,
+    };
+    kind | (piece.color.index() as u8) << 3
+}
+
+/// The pieces of `color` named the way table files are, e.g. "KRP".
+fn material_name(position: &Position, color: TeamColor) -> String {
+    VARIANTS
+        .iter()
+        .zip("KQRBNP".chars())
+        .map(|(variant, letter)| {
+            let count = position.pieces_of(color, *variant).count_ones() as usize;
+            letter.to_string().repeat(count)
+        })
+        .collect()
+}
+
+/// The index of three unique pieces placed with the first in the a1-d1-d4
+/// triangle and the first piece off the a1-h8 diagonal below it.
+fn unique_index(squares: &[usize], indexing: &Indexing) -> u64 {
+    let (first, second, third) = (squares[0], squares[1], squares[2]);
+    let rank = |square: usize| (square / 8) as u64;
+    let adjust_second = (second > first) as u64;
+    let adjust_third = (third > first) as u64 + (third > second) as u64;
+    if off_diagonal(first) != 0 {
+        (indexing.map_a1d1d4[first] as u64 * 63 + (second as u64 - adjust_second)) * 62
+            + third as u64
+            - adjust_third
+    } else if off_diagonal(second) != 0 {
+        (6 * 63 + rank(first) * 28 + indexing.map_b1h1h7[second] as u64) * 62 + third as u64
+            - adjust_third
+    } else if off_diagonal(third) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(first) * 7 * 28
+            + (rank(second) - adjust_second) * 28
+            + indexing.map_b1h1h7[third] as u64
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(first) * 7 * 6
+            + (rank(second) - adjust_second) * 6
+            + (rank(third) - adjust_third)
+    }
+}
+
+/// Where `data` keeps `position`: the side to move and leading pawn file
+/// of the sub-table, and the index of the position within it.
+fn encode(
+    table: &Table,
+    data: &TableData,
+    position: &Position,
+    is_black_first: bool,
+) -> Option<(usize, usize, u64)> {
+    let indexing = indexing();
+
+    // the tables have the side named first as White, and only White to
+    // move when both sides have the same pieces
+    let is_flipped =
+        is_black_first || (table.is_symmetric && position.side_to_move == TeamColor::Black);
+    let (flip_color, flip_squares) = if is_flipped { (8, 56) } else { (0, 0) };
+    let side = position.side_to_move.index() ^ is_flipped as usize;
+
+    let mut squares = [0; MAX_PIECES];
+    let mut pieces = [0; MAX_PIECES];
+    let mut size = 0;
+    let mut lead_pawns = 0;
+    let mut file = 0;
+    if table.has_pawns {
+        // the leading pawns are the first piece of every sub-table; the
+        // one most toward the edges decides the file
+        let code = data.pairs(0, 0).pieces[0] ^ flip_color;
+        let color = if code & 8 == 0 {
+            TeamColor::White
+        } else {
+            TeamColor::Black
+        };
+        lead_pawns = position.pieces_of(color, PieceVariant::Pawn);
+        for square in bitboard::squares(lead_pawns) {
+            *squares.get_mut(size)? = square ^ flip_squares;
+            size += 1;
+        }
+        let lead = (0..size).max_by_key(|i| indexing.map_pawns[squares[*i]])?;
+        squares.swap(0, lead);
+        file = (squares[0] % 8).min(7 - squares[0] % 8);
+    }
+    let lead_pawn_count = size;
+
+    for square in bitboard::squares(position.occupied() & !lead_pawns) {
+        *squares.get_mut(size)? = square ^ flip_squares;
+        pieces[size] = piece_code(position.piece_at(square)?) ^ flip_color;
+        size += 1;
+    }
+
+    // put the pieces in the order of the table
+    let pairs = data.pairs(side, file);
+    for i in lead_pawn_count..size.saturating_sub(1) {
+        if let Some(j) = (i + 1..size).find(|j| pairs.pieces[i] == pieces[*j]) {
+            pieces.swap(i, j);
+            squares.swap(i, j);
+        }
+    }
+
+    // mirror the first piece onto the a-d files
+    if squares[0] % 8 > 3 {
+        for square in &mut squares[..size] {
+            *square ^= 7;
+        }
+    }
+
+    let mut index;
+    if table.has_pawns {
+        index = indexing.lead_pawn_index[lead_pawn_count][squares[0]];
+        squares[1..lead_pawn_count].sort_by_key(|square| indexing.map_pawns[*square]);
+        for (i, square) in squares.iter().enumerate().take(lead_pawn_count).skip(1) {
+            index += indexing.binomial[i][indexing.map_pawns[*square]];
+        }
+    } else {
+        // without pawns the board may also be mirrored onto the first
+        // four ranks and along the a1-h8 diagonal
+        if squares[0] / 8 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 56;
+            }
+        }
+        for i in 0..pairs.group_length[0] {
+            let off = off_diagonal(squares[i]);
+            if off == 0 {
+                continue;
+            }
+            if off > 0 {
+                for square in &mut squares[i..size] {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                }
+            }
+            break;
+        }
+        index = if table.has_unique_pieces {
+            unique_index(&squares, indexing)
+        } else {
+            indexing.map_kk[indexing.map_a1d1d4[squares[0]]][squares[1]] as u64
+        };
+    }
+
+    // every further group counts the squares left by the ones before
+    index *= pairs.group_index[0];
+    let mut start = pairs.group_length[0];
+    let mut has_remaining_pawns = table.has_pawns && table.pawn_counts[1] > 0;
+    let mut next = 1;
+    while pairs.group_length[next] != 0 {
+        let length = pairs.group_length[next];
+        squares.get_mut(start..start + length)?.sort_unstable();
+        let mut group_index = 0;
+        for i in 0..length {
+            let square = squares[start + i];
+            let lower = squares[..start]
+                .iter()
+                .filter(|other| square > **other)
+                .count();
+            let skipped = if has_remaining_pawns { 8 } else { 0 };
+            group_index += indexing.binomial[i + 1][square.checked_sub(lower + skipped)?];
+        }
+        has_remaining_pawns = false;
+        index += group_index * pairs.group_index[next];
+        start += length;
+        next += 1;
+    }
+
+    Some((side, file, index))
+}
+
+/// What a table holds for a position.
+enum Stored {
+    Value(i32),
+    /// The DTZ table only stores positions with the other side to move.
+    OtherSideToMove,
+}
+
+fn is_capture(position: &Position, move_data: BitMove) -> bool {
+    move_data.variant == MoveType::EnPassant || position.piece_at(move_data.to as usize).is_some()
+}
+
+fn is_pawn_move(position: &Position, move_data: BitMove) -> bool {
+    position
+        .piece_at(move_data.from as usize)
+        .is_some_and(|piece| piece.variant == PieceVariant::Pawn)
+}
+
+/// Syzygy endgame tablebases: for every position with few enough pieces,
+/// whether it is won, drawn or lost (WDL) and how many plies it takes the
+/// winning side to the next capture or pawn move (DTZ).
+pub struct Tablebases {
+    /// By their material, e.g. "KRPvKR".
+    tables: HashMap<String, Table>,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Finds the .rtbw files, and the .rtbz files next to them, in
+    /// `directories`, a list separated the way `PATH` is.
+    pub fn open(directories: &str) -> Result<Tablebases, Error> {
+        let mut tables = HashMap::new();
+        for directory in std::env::split_paths(directories) {
+            for entry in std::fs::read_dir(&directory)? {
+                let path = entry?.path();
+                if path.extension() != Some(OsStr::new("rtbw")) {
+                    continue;
+                }
+                let name = match path.file_stem().and_then(OsStr::to_str) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                if let Some(table) = Table::new(&name, &path) {
+                    tables.insert(name, table);
+                }
+            }
+        }
+
+        let max_pieces = tables.values().map(|table| table.piece_count).max();
+        let max_pieces = max_pieces.ok_or(format!("no Syzygy tables in {}", directories))?;
+        Ok(Tablebases { tables, max_pieces })
+    }
+
+    /// The most pieces, kings included, of any table found.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the tables may know `position`: they have no castling rights.
+    fn covers(&self, position: &Position) -> bool {
+        let is_castling_possible = position
+            .is_valid_castling
+            .iter()
+            .flatten()
+            .any(|right| *right);
+        position.occupied().count_ones() as usize <= self.max_pieces && !is_castling_possible
+    }
+
+    /// The table with the material of `position`, and whether it has Black
+    /// as the side named first.
+    fn table_for(&self, position: &Position) -> Option<(&Table, bool)> {
+        let white = material_name(position, TeamColor::White);
+        let black = material_name(position, TeamColor::Black);
+        if let Some(table) = self.tables.get(&format!("{}v{}", white, black)) {
+            return Some((table, false));
+        }
+        let table = self.tables.get(&format!("{}v{}", black, white))?;
+        Some((table, true))
+    }
+
+    /// Reads the value stored for `position`. `wdl` is the result of the
+    /// position, which DTZ values are stored relative to.
+    fn lookup(&self, position: &Position, kind: Kind, wdl: Wdl) -> Option<Stored> {
+        let (table, is_black_first) = self.table_for(position)?;
+        let data = table.data(kind)?;
+        let (side, file, index) = encode(table, data, position, is_black_first)?;
+
+        let pairs = data.pairs(side, file);
+        if kind == Kind::Dtz {
+            let stored_side = (pairs.flags & STORES_BLACK_TO_MOVE) as usize;
+            // by symmetry either side will do when both have the same pieces
+            let is_either_side_stored = table.is_symmetric && !table.has_pawns;
+            if stored_side != side && !is_either_side_stored {
+                return Some(Stored::OtherSideToMove);
+            }
+        }
+
+        let value = pairs.decompress(data, index)?;
+        let value = match kind {
+            Kind::Wdl => value - 2,
+            Kind::Dtz => data.map_dtz(file, value, wdl)?,
+        };
+        Some(Stored::Value(value))
+    }
+
+    fn probe_wdl_table(&self, position: &Position) -> Option<Wdl> {
+        if position.occupied().count_ones() == 2 {
+            return Some(Wdl::Draw);
+        }
+        match self.lookup(position, Kind::Wdl, Wdl::Draw)? {
+            Stored::Value(value) => Wdl::from_value(value),
+            Stored::OtherSideToMove => None,
+        }
+    }
+
+    /// The tables may store anything for positions where a capture is at
+    /// least as good, so the captures are tried as well; with
+    /// `with_pawn_moves` pawn moves too, which DTZ tables leave out. Also
+    /// tells whether such a move is the best one.
+    fn probe_with_zeroing_moves(
+        &self,
+        position: &Position,
+        with_pawn_moves: bool,
+    ) -> Option<(Wdl, bool)> {
+        let moves = position.generate_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut count = 0;
+        for move_data in moves.iter() {
+            let is_zeroing = is_capture(position, *move_data)
+                || (with_pawn_moves && is_pawn_move(position, *move_data));
+            if !is_zeroing {
+                continue;
+            }
+            count += 1;
+
+            let next = position.make_move(*move_data);
+            let (wdl, _) = self.probe_with_zeroing_moves(&next, false)?;
+            let wdl = wdl.opposite();
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        // the stored value cannot be trusted when all moves were tried,
+        // e.g. for en passant, which the tables do not know about
+        let is_every_move_tried = count > 0 && count == moves.len();
+        let stored = if is_every_move_tried {
+            best
+        } else {
+            self.probe_wdl_table(position)?
+        };
+        if best >= stored {
+            Some((best, best > Wdl::Draw || is_every_move_tried))
+        } else {
+            Some((stored, false))
+        }
+    }
+
+    fn dtz(&self, position: &Position) -> Option<i32> {
+        let (wdl, is_zeroing_best) = self.probe_with_zeroing_moves(position, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if is_zeroing_best {
+            return Some(wdl.zeroing_dtz());
+        }
+
+        if let Stored::Value(dtz) = self.lookup(position, Kind::Dtz, wdl)? {
+            let cursed = match wdl {
+                Wdl::CursedWin | Wdl::BlessedLoss => 100,
+                _ => 0,
+            };
+            return Some((dtz + cursed) * wdl.signum());
+        }
+
+        // the table stores the other side to move, so look one move ahead
+        // for the move that keeps the result with the best DTZ
+        let mut best = i32::MAX;
+        for move_data in position.generate_legal_moves().iter() {
+            let is_zeroing = is_capture(position, *move_data) || is_pawn_move(position, *move_data);
+            let next = position.make_move(*move_data);
+            let mut dtz = if is_zeroing {
+                -self.probe_with_zeroing_moves(&next, false)?.0.zeroing_dtz()
+            } else {
+                -self.dtz(&next)?
+            };
+            if dtz == 1
+                && next.is_in_check(next.side_to_move)
+                && next.generate_legal_moves().is_empty()
+            {
+                best = 1;
+            }
+            if !is_zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == wdl.signum() {
+                best = dtz;
+            }
+        }
+        // without legal moves the side to move is mated
+        Some(if best == i32::MAX { -1 } else { best })
+    }
+
+    /// The result of `position` for the side to move, if the tables know it.
+    pub fn probe_wdl(&self, position: &Position) -> Option<Wdl> {
+        if !self.covers(position) {
+            return None;
+        }
+        Some(self.probe_with_zeroing_moves(position, false)?.0)
+    }
+
+    /// Plies until the side to move, when winning, can next capture or move
+    /// a pawn without giving up the win, or until the other side can when
+    /// losing, then negative. Zero for draws. Wins and losses beyond the
+    /// fifty-move rule count 100 plies more.
+    pub fn probe_dtz(&self, position: &Position) -> Option<i32> {
+        if !self.covers(position) {
+            return None;
+        }
+        self.dtz(position)
+    }
+
+    /// The WDL and, where there is a DTZ file, DTZ of `position`.
+    pub fn probe(&self, position: &Position) -> Option<Verdict> {
+        let wdl = self.probe_wdl(position)?;
+        Some(Verdict {
+            side_to_move: position.side_to_move,
+            wdl,
+            dtz: self.probe_dtz(position),
+        })
+    }
+
+    /// The DTZ of the position before `move_data`, as reached through it.
+    fn move_dtz(&self, position: &Position, move_data: BitMove) -> Option<i32> {
+        let next = position.make_move(move_data);
+        let mut dtz = if next.halfmove_clock == 0 {
+            self.probe_with_zeroing_moves(&next, false)?
+                .0
+                .opposite()
+                .zeroing_dtz()
+        } else {
+            let dtz = -self.dtz(&next)?;
+            dtz + dtz.signum()
+        };
+        if dtz == 2 && next.is_in_check(next.side_to_move) && next.generate_legal_moves().is_empty()
+        {
+            dtz = 1;
+        }
+        Some(dtz)
+    }
+
+    /// The legal moves that keep the best result for the side to move: the
+    /// ones quickest to the next capture or pawn move when winning, the ones
+    /// that last longest when losing. Only WDL counts when DTZ is missing.
+    pub fn best_moves(&self, position: &Position) -> Option<Vec<BitMove>> {
+        if !self.covers(position) {
+            return None;
+        }
+        let moves = position.generate_legal_moves();
+        let by_dtz: Option<Vec<i32>> = moves
+            .iter()
+            .map(|move_data| {
+                let dtz = self.move_dtz(position, *move_data)?;
+                Some(match dtz {
+                    0 => 0,
+                    dtz if dtz > 0 => 1000 - dtz,
+                    dtz => -1000 - dtz,
+                })
+            })
+            .collect();
+        let ranks = match by_dtz {
+            Some(ranks) => ranks,
+            None => moves
+                .iter()
+                .map(|move_data| {
+                    let next = position.make_move(*move_data);
+                    let (wdl, _) = self.probe_with_zeroing_moves(&next, false)?;
+                    Some(wdl.opposite() as i32)
+                })
+                .collect::<Option<Vec<i32>>>()?,
+        };
+
+        let best = ranks.iter().max()?;
+        let best_moves = moves
+            .iter()
+            .zip(ranks.iter())
+            .filter(|(_, rank)| *rank == best)
+            .map(|(move_data, _)| *move_data)
+            .collect();
+        Some(best_moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci;
+
+    /// Writes a KRvK table where every position is a win with White to move,
+    /// 31 plies from zeroing, and a loss with Black to move.
+    fn single_value_tables(name: &str) -> Tablebases {
+        let directory =
+            std::env::temp_dir().join(format!("syzygy-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        // split by side to move, pawnless, king, rook and king for both sides
+        let header = [0x01, 0x00, 0x66, 0x44, 0xEE, 0x00];
+        let mut wdl = WDL_MAGIC.to_vec();
+        wdl.extend(header);
+        wdl.extend([SINGLE_VALUE, 4, SINGLE_VALUE, 0]);
+        wdl.resize(64, 0);
+        let mut dtz = DTZ_MAGIC.to_vec();
+        dtz.extend(header);
+        dtz.extend([SINGLE_VALUE, 15]);
+        dtz.resize(64, 0);
+        std::fs::write(directory.join("KRvK.rtbw"), wdl).unwrap();
+        std::fs::write(directory.join("KRvK.rtbz"), dtz).unwrap();
+
+        Tablebases::open(directory.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn index_tables_cover_every_placement() {
+        let indexing = indexing();
+        let king_pairs = indexing.map_kk.iter().flatten().max();
+        assert_eq!(king_pairs, Some(&461));
+        assert_eq!(indexing.map_b1h1h7.iter().max(), Some(&27));
+        assert_eq!(indexing.map_a1d1d4[0], 6, "a1 is on the diagonal");
+        assert_eq!(indexing.binomial[2][5], 10);
+        // 47 squares are left around a single leading pawn on a2
+        assert_eq!(indexing.map_pawns[8], 47);
+        assert_eq!(indexing.lead_pawns_size[1][0], 6);
+    }
+
+    #[test]
+    fn reads_the_material_from_the_name() {
+        let table = Table::new("KRPvKR", Path::new("KRPvKR.rtbw")).unwrap();
+        assert_eq!(table.piece_count, 5);
+        assert!(table.has_pawns && !table.is_symmetric);
+        assert_eq!(table.pawn_counts, [1, 0]);
+        assert!(Table::new("KNNvK", Path::new("KNNvK.rtbw")).is_some_and(|t| !t.has_unique_pieces));
+        assert!(Table::new("KRvKR", Path::new("KRvKR.rtbw")).is_some_and(|t| t.is_symmetric));
+        assert!(Table::new("KXvK", Path::new("KXvK.rtbw")).is_none());
+    }
+
+    #[test]
+    fn probes_either_color_and_tries_captures() {
+        let tablebases = single_value_tables("wdl");
+        assert_eq!(tablebases.max_pieces(), 3);
+
+        let white = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&white), Some(Wdl::Win));
+        let black = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&black), Some(Wdl::Loss));
+        // the same table with the colors the other way around
+        let flipped = Position::from_fen("r3k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&flipped), Some(Wdl::Win));
+        // taking the rook leaves two bare kings
+        let capture = Position::from_fen("8/8/8/8/8/8/3kR3/7K b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&capture), Some(Wdl::Draw));
+
+        let castling = Position::from_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&castling), None);
+        let other_material = Position::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&other_material), None);
+    }
+
+    #[test]
+    fn dtz_looks_ahead_for_the_side_not_stored() {
+        let tablebases = single_value_tables("dtz");
+        let white = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_dtz(&white), Some(31));
+        let black = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_dtz(&black), Some(-32));
+
+        let verdict = tablebases.probe(&white).unwrap();
+        assert_eq!(verdict.to_string(), "White wins in 16");
+        assert_eq!(
+            tablebases.probe(&black).unwrap().to_string(),
+            "White wins in 16"
+        );
+    }
+
+    #[test]
+    fn best_moves_keep_the_best_result() {
+        let tablebases = single_value_tables("moves");
+        let capture = Position::from_fen("8/8/8/8/8/8/3kR3/7K b - - 0 1").unwrap();
+        let moves: Vec<String> = tablebases
+            .best_moves(&capture)
+            .unwrap()
+            .iter()
+            .map(|move_data| uci::render(&move_data.to_move()))
+            .collect();
+        assert_eq!(moves, ["d2e2"]);
+    }
+
+    /// A sub-table to compress, with the value of every index. Indexes no
+    /// position is stored at are left as `None`.
+    struct SubTable {
+        flags: u8,
+        values: Vec<Option<u16>>,
+    }
+
+    impl SubTable {
+        fn new(flags: u8, pairs: &PairsData) -> SubTable {
+            let groups = pairs.group_length.iter().position(|length| *length == 0);
+            let size = pairs.group_index[groups.unwrap()] as usize;
+            SubTable {
+                flags,
+                values: vec![None; size],
+            }
+        }
+
+        /// Stores `value` at `index`, which positions that the encoding
+        /// tells apart must never share.
+        fn store(&mut self, index: u64, value: u16) {
+            let stored = &mut self.values[index as usize];
+            assert!(stored.is_none_or(|stored| stored == value), "{}", index);
+            *stored = Some(value);
+        }
+    }
+
+    const BLOCK_SIZE_BITS: u8 = 5;
+    const SPAN_BITS: u8 = 6;
+
+    /// Compresses a sub-table the way the generator lays it out: a symbol
+    /// for every value, pair symbols for two and four equal values in a row,
+    /// canonical Huffman codes of two lengths, blocks of 32 bytes and a sparse
+    /// index entry every 64 values. Returns the header, the sparse index,
+    /// the block lengths and the blocks.
+    fn compress(sub_table: &SubTable) -> [Vec<u8>; 4] {
+        // positions no one stores repeat the value before them
+        let mut previous = 0;
+        let values: Vec<u16> = sub_table
+            .values
+            .iter()
+            .map(|value| {
+                previous = value.unwrap_or(previous);
+                previous
+            })
+            .collect();
+        let value_count = *values.iter().max().unwrap() as usize + 1;
+
+        // leaves, then pairs of leaves, then pairs of pairs
+        let symbol_count = 3 * value_count;
+        let mut tree = vec![];
+        for value in 0..value_count {
+            tree.push((value, 0xFFF));
+        }
+        for value in 0..value_count {
+            tree.push((value, value));
+        }
+        for value in 0..value_count {
+            tree.push((value_count + value, value_count + value));
+        }
+
+        // the last `short_count` symbols get codes one bit shorter
+        let long_length = symbol_count.next_power_of_two().trailing_zeros() as usize;
+        let short_count = (1 << long_length) - symbol_count;
+        let long_count = symbol_count - short_count;
+        let code = |symbol: usize| {
+            if symbol < long_count {
+                (symbol as u64, long_length)
+            } else {
+                (
+                    (long_count / 2 + symbol - long_count) as u64,
+                    long_length - 1,
+                )
+            }
+        };
+        let lowest_symbols: Vec<u16> = if short_count > 0 {
+            vec![long_count as u16, 0]
+        } else {
+            vec![0]
+        };
+        let min_length = long_length + 1 - lowest_symbols.len();
+
+        let block_size = 1 << BLOCK_SIZE_BITS;
+        let mut blocks: Vec<(Vec<u8>, usize)> = vec![];
+        let mut start = 0;
+        while start < values.len() {
+            let mut block = vec![0; block_size];
+            let (mut bits, mut count) = (0, 0);
+            while start + count < values.len() {
+                let value = values[start + count] as usize;
+                let run = [4, 2, 1]
+                    .into_iter()
+                    .find(|run| {
+                        values
+                            .get(start + count..start + count + run)
+                            .is_some_and(|next| next.iter().all(|other| *other as usize == value))
+                    })
+                    .unwrap();
+                let symbol = value_count * run.trailing_zeros() as usize + value;
+                let (symbol_code, length) = code(symbol);
+                if bits + length > 8 * block_size {
+                    break;
+                }
+                for i in 0..length {
+                    if symbol_code >> (length - 1 - i) & 1 != 0 {
+                        block[(bits + i) / 8] |= 0x80 >> ((bits + i) % 8);
+                    }
+                }
+                bits += length;
+                count += run;
+            }
+            blocks.push((block, count));
+            start += count;
+        }
+
+        let mut header = vec![sub_table.flags, BLOCK_SIZE_BITS, SPAN_BITS, 0];
+        header.extend((blocks.len() as u32).to_le_bytes());
+        header.extend([long_length as u8, min_length as u8]);
+        for lowest in lowest_symbols {
+            header.extend(lowest.to_le_bytes());
+        }
+        header.extend((symbol_count as u16).to_le_bytes());
+        for (left, right) in tree {
+            header.extend([
+                left as u8,
+                (left >> 8 | (right & 0xF) << 4) as u8,
+                (right >> 4) as u8,
+            ]);
+        }
+        if symbol_count % 2 == 1 {
+            header.push(0);
+        }
+
+        // every entry points at the middle of its span
+        let span = 1 << SPAN_BITS;
+        let mut block_starts = vec![];
+        let mut start = 0;
+        for (_, count) in &blocks {
+            block_starts.push(start);
+            start += count;
+        }
+        let mut sparse_index = vec![];
+        for middle in (span / 2..values.len() + span / 2).step_by(span) {
+            let block = block_starts
+                .iter()
+                .rposition(|start| *start <= middle)
+                .unwrap();
+            sparse_index.extend((block as u32).to_le_bytes());
+            sparse_index.extend(((middle - block_starts[block]) as u16).to_le_bytes());
+        }
+
+        let block_lengths = blocks
+            .iter()
+            .flat_map(|(_, count)| (*count as u16 - 1).to_le_bytes())
+            .collect();
+        let data = blocks.into_iter().flat_map(|(block, _)| block).collect();
+        [header, sparse_index, block_lengths, data]
+    }
+
+    /// Writes a table file with the pieces and orders in `header`, the
+    /// compressed sub-tables and, for DTZ, the value maps.
+    fn write_table(path: &Path, kind: Kind, header: &[u8], sub_tables: &[SubTable], maps: &[u8]) {
+        let mut bytes = match kind {
+            Kind::Wdl => WDL_MAGIC.to_vec(),
+            Kind::Dtz => DTZ_MAGIC.to_vec(),
+        };
+        bytes.extend(header);
+        bytes.resize(bytes.len().next_multiple_of(2), 0);
+
+        let compressed: Vec<[Vec<u8>; 4]> = sub_tables.iter().map(compress).collect();
+        for part in 0..3 {
+            for sub_table in &compressed {
+                bytes.extend(&sub_table[part]);
+            }
+            if part == 0 && kind == Kind::Dtz {
+                bytes.extend(maps);
+                bytes.resize(bytes.len().next_multiple_of(2), 0);
+            }
+        }
+        for sub_table in &compressed {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            bytes.extend(&sub_table[3]);
+        }
+        // the decoder reads a little past the symbol it stops at
+        bytes.extend([0; 8]);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    /// The layout the prober reads from `header`, which says at which
+    /// sub-table and index every position is stored.
+    fn layout(directory: &Path, kind: Kind, header: &[u8], table: &Table) -> TableData {
+        let sides = if kind == Kind::Wdl && !table.is_symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if table.has_pawns { 4 } else { 1 };
+        let mut bytes = match kind {
+            Kind::Wdl => WDL_MAGIC.to_vec(),
+            Kind::Dtz => DTZ_MAGIC.to_vec(),
+        };
+        bytes.extend(header);
+        bytes.resize(bytes.len().next_multiple_of(2), 0);
+        for _ in 0..sides * files {
+            bytes.extend([SINGLE_VALUE, 0]);
+        }
+        bytes.resize(bytes.len() + 64 * sides * files, 0);
+        let path = directory.join("layout");
+        std::fs::write(&path, bytes).unwrap();
+        TableData::load(&path, kind, table).unwrap()
+    }
+
+    fn table_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("syzygy-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn place(pieces: &[(TeamColor, PieceVariant, usize)], side_to_move: TeamColor) -> Position {
+        let mut position = Position {
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
+            side_to_move,
+            is_valid_castling: [[false; 2]; 2],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+        };
+        for (color, variant, square) in pieces {
+            position.pieces[color.index()][variant.index()] |= 1 << square;
+            position.occupancy[color.index()] |= 1 << square;
+        }
+        position
+    }
+
+    /// The same position with the colors the other way around.
+    fn flip(pieces: &[(TeamColor, PieceVariant, usize)], side_to_move: TeamColor) -> Position {
+        let flipped: Vec<_> = pieces
+            .iter()
+            .map(|(color, variant, square)| (color.opposite(), *variant, square ^ 56))
+            .collect();
+        place(&flipped, side_to_move.opposite())
+    }
+
+    /// Where `square` goes under one of the eight symmetries of the board:
+    /// bit 0 mirrors the files, bit 1 the ranks and bit 2 the a1-h8 diagonal.
+    fn transform(square: usize, symmetry: usize) -> usize {
+        let mut square = square;
+        if symmetry & 1 != 0 {
+            square ^= 7;
+        }
+        if symmetry & 2 != 0 {
+            square ^= 56;
+        }
+        if symmetry & 4 != 0 {
+            square = ((square >> 3) | (square << 3)) & 63;
+        }
+        square
+    }
+
+    fn random_square(seed: &mut u64) -> usize {
+        *seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (*seed >> 58) as usize
+    }
+
+    fn stored_value(stored: Option<Stored>) -> Option<i32> {
+        match stored? {
+            Stored::Value(value) => Some(value),
+            Stored::OtherSideToMove => None,
+        }
+    }
+
+    /// KRvK is won from every position but those where the rook is lost
+    /// right away or the defending king is stalemated.
+    fn krvk_wdl(position: &Position) -> Wdl {
+        let rook_color = if position.pieces_of(TeamColor::White, PieceVariant::Castle) != 0 {
+            TeamColor::White
+        } else {
+            TeamColor::Black
+        };
+        if position.side_to_move == rook_color {
+            return Wdl::Win;
+        }
+        let moves = position.generate_legal_moves();
+        let takes_rook = moves
+            .iter()
+            .any(|move_data| position.piece_at(move_data.to as usize).is_some());
+        let is_stalemate = moves.is_empty() && !position.is_in_check(position.side_to_move);
+        if takes_rook || is_stalemate {
+            Wdl::Draw
+        } else {
+            Wdl::Loss
+        }
+    }
+
+    #[test]
+    fn compressed_tables_give_the_krvk_results() {
+        let directory = table_directory("krvk");
+        let path = directory.join("KRvK.rtbw");
+        let table = Table::new("KRvK", &path).unwrap();
+        let header = [SPLIT, 0x00, 0x66, 0x44, 0xEE];
+        let layout = layout(&directory, Kind::Wdl, &header, &table);
+        let mut sub_tables: Vec<SubTable> = (0..2)
+            .map(|side| SubTable::new(0, layout.pairs(side, 0)))
+            .collect();
+
+        let mut placements = vec![];
+        for king in 0..64 {
+            for rook in (0..64).filter(|rook| *rook != king) {
+                for other in (0..64).filter(|other| ![king, rook].contains(other)) {
+                    for side_to_move in [TeamColor::White, TeamColor::Black] {
+                        let pieces = [
+                            (TeamColor::White, PieceVariant::King, king),
+                            (TeamColor::White, PieceVariant::Castle, rook),
+                            (TeamColor::Black, PieceVariant::King, other),
+                        ];
+                        let position = place(&pieces, side_to_move);
+                        if position.is_in_check(side_to_move.opposite()) {
+                            continue;
+                        }
+                        let (side, file, index) =
+                            encode(&table, &layout, &position, false).unwrap();
+                        assert_eq!(file, 0);
+                        sub_tables[side].store(index, (krvk_wdl(&position) as i32 + 2) as u16);
+
+                        // every symmetry of the board is stored once
+                        let symmetry = (king + rook + other) % 8;
+                        let image = pieces.map(|(color, variant, square)| {
+                            (color, variant, transform(square, symmetry))
+                        });
+                        let image = place(&image, side_to_move);
+                        let image_index = encode(&table, &layout, &image, false);
+                        assert_eq!(image_index, Some((side, file, index)));
+
+                        placements.push((pieces, side_to_move));
+                    }
+                }
+            }
+        }
+        write_table(&path, Kind::Wdl, &header, &sub_tables, &[]);
+
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        for (pieces, side_to_move) in placements.into_iter().step_by(11) {
+            let position = place(&pieces, side_to_move);
+            assert_eq!(tablebases.probe_wdl(&position), Some(krvk_wdl(&position)));
+            let flipped = flip(&pieces, side_to_move);
+            assert_eq!(tablebases.probe_wdl(&flipped), Some(krvk_wdl(&flipped)));
+        }
+    }
+
+    #[test]
+    fn two_kings_and_a_pair_are_indexed_apart() {
+        let directory = table_directory("knnvk");
+        let table = Table::new("KNNvK", &directory.join("KNNvK.rtbw")).unwrap();
+        let header = [SPLIT, 0x00, 0x66, 0xEE, 0x22, 0x22];
+        let layout = layout(&directory, Kind::Wdl, &header, &table);
+        let size = SubTable::new(0, layout.pairs(0, 0)).values.len();
+        assert_eq!(size, 462 * 1891);
+
+        let mut placements = HashMap::new();
+        let mut seed = 1;
+        for _ in 0..20_000 {
+            let squares: [usize; 4] = std::array::from_fn(|_| random_square(&mut seed));
+            let [king, other, first, second] = squares;
+            let is_touching = bitboard::king_attacks(king) & 1 << other != 0;
+            if is_touching || (1..4).any(|i| squares[..i].contains(&squares[i])) {
+                continue;
+            }
+            let pieces = |symmetry: usize| {
+                let [king, other, first, second] =
+                    squares.map(|square| transform(square, symmetry));
+                [
+                    (TeamColor::White, PieceVariant::King, king),
+                    (TeamColor::Black, PieceVariant::King, other),
+                    (TeamColor::White, PieceVariant::Knight, first.min(second)),
+                    (TeamColor::White, PieceVariant::Knight, first.max(second)),
+                ]
+            };
+            let position = place(&pieces(0), TeamColor::White);
+            let (_, _, index) = encode(&table, &layout, &position, false).unwrap();
+            assert!((index as usize) < size);
+
+            // the placement the same up to symmetry, which no other may share
+            let canonical = (0..8)
+                .map(pieces)
+                .min_by_key(|pieces| pieces.map(|piece| piece.2));
+            let canonical = canonical.unwrap().map(|piece| piece.2);
+            assert_eq!(*placements.entry(index).or_insert(canonical), canonical);
+
+            // with both kings on a diagonal the rest is stored both ways
+            let diagonals = [off_diagonal(king) == 0, (king % 8 + king / 8) == 7];
+            let on_diagonal = [off_diagonal(other) == 0, (other % 8 + other / 8) == 7];
+            if diagonals[0] && on_diagonal[0] || diagonals[1] && on_diagonal[1] {
+                continue;
+            }
+            let image = place(&pieces((first + second) % 8), TeamColor::White);
+            let image_index = encode(&table, &layout, &image, false);
+            assert_eq!(image_index, Some((0, 0, index)));
+        }
+        assert!(placements.len() > 10_000);
+    }
+
+    /// Some value of a KPvK placement, only there to tell placements apart.
+    fn kpvk_value(pawn: usize, king: usize, other: usize, side_to_move: usize) -> u16 {
+        ((pawn / 8 + king + 2 * other + side_to_move) % 5) as u16
+    }
+
+    #[test]
+    fn pawn_tables_keep_every_file_apart() {
+        let directory = table_directory("kpvk");
+        let path = directory.join("KPvK.rtbw");
+        let table = Table::new("KPvK", &path).unwrap();
+        let mut header = vec![SPLIT | HAS_PAWNS];
+        for _ in 0..4 {
+            header.extend([0x00, 0x11, 0x66, 0xEE]);
+        }
+        let layout = layout(&directory, Kind::Wdl, &header, &table);
+        let mut sub_tables: Vec<SubTable> = (0..8)
+            .map(|i| SubTable::new(0, layout.pairs(i % 2, i / 2)))
+            .collect();
+
+        let mut placements = vec![];
+        for pawn in 8..56 {
+            for king in (0..64).filter(|king| *king != pawn) {
+                for other in (0..64).filter(|other| ![pawn, king].contains(other)) {
+                    for side_to_move in [TeamColor::White, TeamColor::Black] {
+                        let pieces = [
+                            (TeamColor::White, PieceVariant::Pawn, pawn),
+                            (TeamColor::White, PieceVariant::King, king),
+                            (TeamColor::Black, PieceVariant::King, other),
+                        ];
+                        let position = place(&pieces, side_to_move);
+                        let (side, file, index) =
+                            encode(&table, &layout, &position, false).unwrap();
+                        assert_eq!(side, side_to_move.index());
+                        assert_eq!(file, (pawn % 8).min(7 - pawn % 8));
+
+                        // pawns only mirror from one side of the board to the other
+                        let [pawn, king, other] = if pawn % 8 > 3 {
+                            [pawn ^ 7, king ^ 7, other ^ 7]
+                        } else {
+                            [pawn, king, other]
+                        };
+                        let value = kpvk_value(pawn, king, other, side);
+                        sub_tables[2 * file + side].store(index, value);
+                        placements.push((pieces, side_to_move, value));
+                    }
+                }
+            }
+        }
+        write_table(&path, Kind::Wdl, &header, &sub_tables, &[]);
+
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        for (pieces, side_to_move, value) in placements.into_iter().step_by(7) {
+            let expected = Some(value as i32 - 2);
+            let position = place(&pieces, side_to_move);
+            let stored = tablebases.lookup(&position, Kind::Wdl, Wdl::Draw);
+            assert_eq!(stored_value(stored), expected);
+            let flipped = flip(&pieces, side_to_move);
+            let stored = tablebases.lookup(&flipped, Kind::Wdl, Wdl::Draw);
+            assert_eq!(stored_value(stored), expected);
+        }
+    }
+
+    #[test]
+    fn dtz_maps_turn_stored_values_into_plies() {
+        let directory = table_directory("maps");
+        let path = directory.join("KPvK.rtbw");
+        let table = Table::new("KPvK", &path).unwrap();
+        let mut header = vec![SPLIT | HAS_PAWNS];
+        for _ in 0..4 {
+            header.extend([0x00, 0x11, 0x66, 0xEE]);
+        }
+        let layout = layout(&directory, Kind::Dtz, &header, &table);
+
+        // byte maps, wide maps, maps already in plies and no maps at all
+        let flags = [MAPPED, MAPPED | WIDE, MAPPED | WIN_PLIES | LOSS_PLIES, 0];
+        let map_value = |file: usize, map: usize, value: usize| 40 * file + 10 * map + value + 1;
+        let mut maps = vec![];
+        for (file, flags) in flags.iter().enumerate() {
+            if flags & MAPPED == 0 {
+                continue;
+            }
+            if flags & WIDE != 0 {
+                maps.resize(maps.len().next_multiple_of(2), 0);
+            }
+            for map in 0..4 {
+                let entries = (0..3).map(|value| map_value(file, map, value));
+                if flags & WIDE != 0 {
+                    maps.extend(3u16.to_le_bytes());
+                    maps.extend(entries.flat_map(|entry| (entry as u16 + 300).to_le_bytes()));
+                } else {
+                    maps.push(3);
+                    maps.extend(entries.map(|entry| entry as u8));
+                }
+            }
+        }
+        let mut sub_tables: Vec<SubTable> = (0..4)
+            .map(|file| SubTable::new(flags[file], layout.pairs(0, file)))
+            .collect();
+
+        let mut placements = vec![];
+        let mut seed = 7;
+        for _ in 0..5_000 {
+            let [pawn, king, other] = [0; 3].map(|_| random_square(&mut seed));
+            if !(8..56).contains(&pawn) || king == pawn || [pawn, king].contains(&other) {
+                continue;
+            }
+            let pieces = [
+                (TeamColor::White, PieceVariant::Pawn, pawn),
+                (TeamColor::White, PieceVariant::King, king),
+                (TeamColor::Black, PieceVariant::King, other),
+            ];
+            let position = place(&pieces, TeamColor::White);
+            let (_, file, index) = encode(&table, &layout, &position, false).unwrap();
+            let mirror = if pawn % 8 > 3 { 7 } else { 0 };
+            let value = ((king ^ mirror) + (other ^ mirror)) % 3;
+            sub_tables[file].store(index, value as u16);
+            placements.push((pieces, file, value));
+        }
+        write_table(
+            &path.with_extension("rtbz"),
+            Kind::Dtz,
+            &header,
+            &sub_tables,
+            &maps,
+        );
+        // only the DTZ file is read, but tables are found by their WDL file
+        std::fs::write(&path, WDL_MAGIC).unwrap();
+
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        for (pieces, file, value) in placements {
+            let position = place(&pieces, TeamColor::White);
+            for (map, wdl) in [Wdl::Win, Wdl::Loss, Wdl::CursedWin, Wdl::BlessedLoss]
+                .into_iter()
+                .enumerate()
+            {
+                let mapped = match flags[file] {
+                    0 => value,
+                    flags if flags & WIDE != 0 => map_value(file, map, value) + 300,
+                    _ => map_value(file, map, value),
+                };
+                let is_in_plies = flags[file] & WIN_PLIES != 0 && map < 2;
+                let plies = if is_in_plies { mapped } else { 2 * mapped };
+                let stored = tablebases.lookup(&position, Kind::Dtz, wdl);
+                assert_eq!(stored_value(stored), Some(plies as i32 + 1), "{:?}", wdl);
+            }
+
+            let black = place(&pieces, TeamColor::Black);
+            let stored = tablebases.lookup(&black, Kind::Dtz, Wdl::Win);
+            assert!(matches!(stored, Some(Stored::OtherSideToMove)));
+        }
+    }
+}
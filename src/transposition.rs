@@ -0,0 +1,201 @@
+use crate::bitboard::BitMove;
+use crate::search;
+
+use std::mem;
+
+/// Memory given to the table when nothing else is asked for.
+pub const DEFAULT_MEGABYTES: usize = 16;
+const ENTRIES_PER_BUCKET: usize = 2;
+
+/// How the stored score relates to the true score of the position.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high: the true score is at least this.
+    Lower,
+    /// The search failed low: the true score is at most this.
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub best_move: Option<BitMove>,
+    /// Stored relative to the position, see `score_to_table`.
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+    /// The search the entry was written in, to tell stale entries apart.
+    generation: u8,
+}
+
+/// Mate scores count plies from the root, which differs between the paths
+/// that reach a position. The table keeps them counted from the position
+/// itself instead.
+pub fn score_to_table(score: i32, ply: i32) -> i32 {
+    if search::is_mate_score(score) {
+        score + score.signum() * ply
+    } else {
+        score
+    }
+}
+
+pub fn score_from_table(score: i32, ply: i32) -> i32 {
+    if search::is_mate_score(score) {
+        score - score.signum() * ply
+    } else {
+        score
+    }
+}
+
+/// A fixed-size hash table of search results keyed by Zobrist hash. Every
+/// bucket holds one slot that keeps the deepest result of the current search
+/// and one that always takes the newest.
+pub struct TranspositionTable {
+    buckets: Vec<[Option<Entry>; ENTRIES_PER_BUCKET]>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Makes a table of at most `megabytes` of memory, rounded down to a power
+    /// of two number of buckets.
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let bucket_size = mem::size_of::<[Option<Entry>; ENTRIES_PER_BUCKET]>();
+        let wanted = (megabytes * 1024 * 1024 / bucket_size).max(1);
+        let bucket_count = if wanted.is_power_of_two() {
+            wanted
+        } else {
+            wanted.next_power_of_two() / 2
+        };
+
+        TranspositionTable {
+            buckets: vec![[None; ENTRIES_PER_BUCKET]; bucket_count],
+            generation: 0,
+        }
+    }
+
+    /// Marks the entries written so far as older than the ones to come.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn bucket_index(&self, key: u64) -> usize {
+        key as usize & (self.buckets.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.buckets[self.bucket_index(key)]
+            .iter()
+            .flatten()
+            .find(|entry| entry.key == key)
+            .copied()
+    }
+
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<BitMove>,
+    ) {
+        let generation = self.generation;
+        let index = self.bucket_index(key);
+        let bucket = &mut self.buckets[index];
+
+        // keep the move of an earlier search of this position if this one
+        // did not find one
+        let best_move = best_move.or_else(|| {
+            bucket
+                .iter()
+                .flatten()
+                .find(|entry| entry.key == key)
+                .and_then(|entry| entry.best_move)
+        });
+        let entry = Entry {
+            key,
+            best_move,
+            score,
+            depth,
+            bound,
+            generation,
+        };
+
+        let slot = match bucket[0] {
+            None => 0,
+            Some(kept) if kept.key == key => 0,
+            Some(kept) if kept.generation != generation || kept.depth <= depth => {
+                // the displaced entry still gets a chance in the other slot
+                bucket[1] = Some(kept);
+                0
+            }
+            Some(_) => 1,
+        };
+        bucket[slot] = Some(entry);
+        if slot == 0 && bucket[1].is_some_and(|other| other.key == key) {
+            bucket[1] = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    #[test]
+    fn size_is_a_power_of_two_within_the_budget() {
+        let table = TranspositionTable::new(1);
+        assert!(table.buckets.len().is_power_of_two());
+        assert!(table.buckets.len() * mem::size_of::<[Option<Entry>; 2]>() <= 1024 * 1024);
+    }
+
+    #[test]
+    fn stores_and_probes() {
+        let mut table = TranspositionTable::new(1);
+        let key = 0xDEAD_BEEF_1234_5678;
+        assert!(table.probe(key).is_none());
+
+        table.store(key, 3, Bound::Lower, 42, Some(BitMove::NULL));
+        let entry = table.probe(key).unwrap();
+        assert_eq!(
+            (entry.depth, entry.bound, entry.score),
+            (3, Bound::Lower, 42)
+        );
+        assert_eq!(entry.best_move, Some(BitMove::NULL));
+
+        table.store(key, 4, Bound::Exact, 7, None);
+        let entry = table.probe(key).unwrap();
+        assert_eq!((entry.depth, entry.score), (4, 7));
+        assert_eq!(entry.best_move, Some(BitMove::NULL));
+    }
+
+    #[test]
+    fn deep_entries_survive_shallow_ones_of_the_same_search() {
+        let mut table = TranspositionTable::new(1);
+        let buckets = table.buckets.len() as u64;
+        let (deep, shallow, newest) = (5, 5 + buckets, 5 + 2 * buckets);
+
+        table.store(deep, 8, Bound::Exact, 1, None);
+        table.store(shallow, 2, Bound::Exact, 2, None);
+        table.store(newest, 1, Bound::Exact, 3, None);
+        assert!(table.probe(deep).is_some());
+        assert!(table.probe(shallow).is_none());
+        assert!(table.probe(newest).is_some());
+
+        // in a later search the old deep entry may go
+        table.new_search();
+        table.store(shallow, 2, Bound::Exact, 2, None);
+        assert_eq!(table.probe(shallow).unwrap().score, 2);
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_position() {
+        let mate_in_three_from_root = MATE - 5;
+        let stored = score_to_table(mate_in_three_from_root, 2);
+        assert_eq!(stored, MATE - 3);
+        assert_eq!(score_from_table(stored, 4), MATE - 7);
+        assert_eq!(score_from_table(score_to_table(-MATE + 6, 3), 3), -MATE + 6);
+        assert_eq!(score_to_table(120, 9), 120);
+    }
+}
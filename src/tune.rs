@@ -0,0 +1,335 @@
+use crate::bitboard::Position;
+use crate::evaluate;
+use crate::fen::FenError;
+use crate::game::{PieceVariant, TeamColor};
+use crate::parameters::Parameters;
+
+use std::fmt;
+use std::thread;
+
+/// A position from a finished game and how that game ended.
+pub struct LabeledPosition {
+    pub position: Position,
+    /// 1 when White won, 0.5 for a draw and 0 when Black won.
+    pub result: f64,
+}
+
+/// What is wrong with a line of a labeled position file.
+#[derive(Debug, PartialEq)]
+pub enum LabelError {
+    MissingResult(usize),
+    InvalidFen { line: usize, error: FenError },
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LabelError::MissingResult(line) => {
+                write!(f, "line {}: no game result after the position", line)
+            }
+            LabelError::InvalidFen { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for LabelError {}
+
+/// Reads a result such as "1-0", "1/2-1/2" or "0.5", also when quoted or
+/// wrapped in brackets, as the score of White.
+fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| matches!(c, '"' | '[' | ']' | ';')) {
+        "1-0" | "1.0" => Some(1.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        "0-1" | "0.0" => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Reads one position per line: a FEN string followed by the result of the
+/// game it was taken from, e.g. `<fen> [0.5]` or `<fen> c9 "1-0";`. Empty
+/// lines are skipped.
+pub fn parse_positions(text: &str) -> Result<Vec<LabeledPosition>, LabelError> {
+    let mut positions = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+
+        // the halfmove clock and fullmove number are optional, and so is
+        // anything between the position and the result
+        let has_counters =
+            tokens.len() > 6 && tokens[4..6].iter().all(|t| t.parse::<u32>().is_ok());
+        let fen_length = if has_counters { 6 } else { 4 };
+        let fen = tokens[..fen_length.min(tokens.len())].join(" ");
+        let result = tokens
+            .get(fen_length..)
+            .unwrap_or_default()
+            .iter()
+            .find_map(|token| parse_result(token))
+            .ok_or(LabelError::MissingResult(line_number))?;
+
+        let position = Position::from_fen(&fen).map_err(|error| LabelError::InvalidFen {
+            line: line_number,
+            error,
+        })?;
+        positions.push(LabeledPosition { position, result });
+    }
+    Ok(positions)
+}
+
+/// The expected result for White of a position evaluated at `score`
+/// centipawns for White. `scaling` maps centipawns to winning chances.
+fn expected_result(score: i32, scaling: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * score as f64 / 400.0))
+}
+
+/// The mean squared difference between the game results and the results
+/// the static evaluation predicts, spread over all cores.
+pub fn mean_squared_error(
+    positions: &[LabeledPosition],
+    parameters: &Parameters,
+    scaling: f64,
+) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let threads = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk_size = positions.len().div_ceil(threads);
+    let total: f64 = thread::scope(|scope| {
+        let workers: Vec<_> = positions
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|labeled| {
+                            let score = evaluate::evaluate(&labeled.position, parameters);
+                            let score = match labeled.position.side_to_move {
+                                TeamColor::White => score,
+                                TeamColor::Black => -score,
+                            };
+                            (labeled.result - expected_result(score, scaling)).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum()
+    });
+    total / positions.len() as f64
+}
+
+/// Finds the scaling under which `parameters` predict the results best, so
+/// the tuning afterwards does not just stretch all the weights.
+pub fn fit_scaling(positions: &[LabeledPosition], parameters: &Parameters) -> f64 {
+    let mut scaling = 1.0;
+    let mut error = mean_squared_error(positions, parameters, scaling);
+    for step in [0.1, 0.01, 0.001] {
+        loop {
+            let candidates = [scaling - step, scaling + step];
+            let (candidate, candidate_error) = candidates
+                .iter()
+                .filter(|candidate| **candidate > 0.0)
+                .map(|candidate| {
+                    (
+                        *candidate,
+                        mean_squared_error(positions, parameters, *candidate),
+                    )
+                })
+                .fold((scaling, error), |best, tried| {
+                    if tried.1 < best.1 {
+                        tried
+                    } else {
+                        best
+                    }
+                });
+            if candidate_error >= error {
+                break;
+            }
+            scaling = candidate;
+            error = candidate_error;
+        }
+    }
+    scaling
+}
+
+/// Whether the weight at `index` of the term `name` can change the error at
+/// all. Both sides always have their king, pawns never stand on rank 1 or 8,
+/// and a passed pawn is neither on its first rank nor still a pawn on its
+/// last.
+fn is_tunable(name: &str, index: usize) -> bool {
+    match name.split_once('.').map_or(name, |(term, _)| term) {
+        "material" => index != PieceVariant::King.index(),
+        "pawn_squares" => (8..56).contains(&index),
+        "passed_pawn" => (1..7).contains(&index),
+        _ => true,
+    }
+}
+
+fn weight_count(parameters: &Parameters) -> usize {
+    parameters
+        .clone()
+        .terms_mut()
+        .iter()
+        .map(|(name, values)| {
+            (0..values.len())
+                .filter(|&index| is_tunable(name, index))
+                .count()
+        })
+        .sum()
+}
+
+fn weight_mut(parameters: &mut Parameters, index: usize) -> &mut i32 {
+    parameters
+        .terms_mut()
+        .into_iter()
+        .flat_map(|(name, values)| {
+            values
+                .iter_mut()
+                .enumerate()
+                .filter(move |(index, _)| is_tunable(&name, *index))
+                .map(|(_, value)| value)
+        })
+        .nth(index)
+        .expect("weight index out of range")
+}
+
+/// Texel's local search: every weight is nudged up by one, or down if that
+/// does not help, and kept wherever the error drops. Runs at most `passes`
+/// times over all weights, or until a pass changes nothing, and calls
+/// `after_pass` with the pass number and the error reached. Returns the
+/// final error.
+pub fn tune(
+    positions: &[LabeledPosition],
+    parameters: &mut Parameters,
+    scaling: f64,
+    passes: usize,
+    mut after_pass: impl FnMut(usize, f64, &Parameters),
+) -> f64 {
+    let mut error = mean_squared_error(positions, parameters, scaling);
+    for pass in 1..=passes {
+        let mut is_improved = false;
+        for index in 0..weight_count(parameters) {
+            for step in [1, -1] {
+                *weight_mut(parameters, index) += step;
+                let new_error = mean_squared_error(positions, parameters, scaling);
+                if new_error < error {
+                    error = new_error;
+                    is_improved = true;
+                    break;
+                }
+                *weight_mut(parameters, index) -= step;
+            }
+        }
+
+        after_pass(pass, error, parameters);
+        if !is_improved {
+            break;
+        }
+    }
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELED: &str = "\
+4k3/8/8/8/8/8/PPPPPPPP/4K3 w - - 0 1 [1.0]
+4k3/pppppppp/8/8/8/8/8/4K3 w - - c9 \"0-1\";
+
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 1/2-1/2
+";
+
+    #[test]
+    fn reads_the_usual_label_formats() {
+        let positions = parse_positions(LABELED).unwrap();
+        let results: Vec<f64> = positions.iter().map(|labeled| labeled.result).collect();
+        assert_eq!(results, [1.0, 0.0, 0.5]);
+
+        assert_eq!(
+            parse_positions("4k3/8/8/8/8/8/8/4K3 w - - 0 1").err(),
+            Some(LabelError::MissingResult(1))
+        );
+        assert!(matches!(
+            parse_positions("\n4k3/8/8 w - - 1-0"),
+            Err(LabelError::InvalidFen { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn better_predictions_have_a_lower_error() {
+        let positions = parse_positions(LABELED).unwrap();
+        let flipped = parse_positions(&LABELED.replace("[1.0]", "[0.0]")).unwrap();
+        let parameters = Parameters::default();
+        assert!(
+            mean_squared_error(&positions, &parameters, 1.0)
+                < mean_squared_error(&flipped, &parameters, 1.0)
+        );
+    }
+
+    #[test]
+    fn skips_weights_that_cannot_change_the_error() {
+        let mut parameters = Parameters::default();
+        for index in 0..weight_count(&parameters) {
+            *weight_mut(&mut parameters, index) = i32::MIN;
+        }
+
+        let default = Parameters::default();
+        for phase in 0..2 {
+            let king = PieceVariant::King.index();
+            let pawn = PieceVariant::Pawn.index();
+            assert_eq!(
+                parameters.material[phase][king],
+                default.material[phase][king]
+            );
+            assert_eq!(parameters.material[phase][pawn], i32::MIN);
+            for square in (0..8).chain(56..64) {
+                assert_eq!(
+                    parameters.piece_squares[phase][pawn][square],
+                    default.piece_squares[phase][pawn][square]
+                );
+            }
+            assert!(parameters.piece_squares[phase][pawn][8..56]
+                .iter()
+                .all(|&value| value == i32::MIN));
+            assert_eq!(
+                parameters.passed_pawn[phase][0],
+                default.passed_pawn[phase][0]
+            );
+            assert_eq!(
+                parameters.passed_pawn[phase][7],
+                default.passed_pawn[phase][7]
+            );
+            assert!(parameters.passed_pawn[phase][1..7]
+                .iter()
+                .all(|&value| value == i32::MIN));
+        }
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        // eight extra pawns that did not win, so the weights have to move
+        let positions = parse_positions(
+            "4k3/8/8/8/8/8/PPPPPPPP/4K3 w - - 0 1 [0.5]\n\
+             4k3/8/8/8/8/8/8/1N2K3 w - - 0 1 [1.0]",
+        )
+        .unwrap();
+        let mut parameters = Parameters::default();
+        let scaling = fit_scaling(&positions, &parameters);
+        let before = mean_squared_error(&positions, &parameters, scaling);
+
+        let mut passes = vec![];
+        let after = tune(&positions, &mut parameters, scaling, 1, |pass, error, _| {
+            passes.push((pass, error))
+        });
+        assert!(after < before);
+        assert_eq!(passes, [(1, after)]);
+        assert_eq!(mean_squared_error(&positions, &parameters, scaling), after);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::game::{self, GameData, PieceVariant};
+use crate::produce::{Move, MoveType};
+
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum UciError {
+    Malformed(String),
+    Illegal(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciError::Malformed(text) => write!(f, "'{}' is not a UCI move", text),
+            UciError::Illegal(text) => write!(f, "'{}' is not a legal move here", text),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+/// Writes `move_data` in UCI long algebraic notation, e.g. "e2e4" or "e7e8q".
+/// Castling is written as the two-square king move it is stored as.
+pub fn render(move_data: &Move) -> String {
+    let mut uci = game::square_name(move_data.from) + &game::square_name(move_data.to);
+    if let MoveType::Promotion(variant) = move_data.variant {
+        uci.extend(promotion_letter(variant));
+    }
+    uci
+}
+
+fn promotion_letter(variant: PieceVariant) -> Option<char> {
+    match variant {
+        PieceVariant::Queen => Some('q'),
+        PieceVariant::Castle => Some('r'),
+        PieceVariant::Bishop => Some('b'),
+        PieceVariant::Knight => Some('n'),
+        // pawns never promote to these
+        PieceVariant::King | PieceVariant::Pawn => None,
+    }
+}
+
+/// Resolves a UCI move against the legal moves in `data`.
+pub fn parse(data: &GameData, text: &str) -> Result<Move, UciError> {
+    let malformed = || UciError::Malformed(text.to_string());
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+        return Err(malformed());
+    }
+
+    let from = game::parse_square(&text[0..2]).ok_or_else(malformed)?;
+    let to = game::parse_square(&text[2..4]).ok_or_else(malformed)?;
+    let promotion = match text.as_bytes().get(4) {
+        Some(b'q') => Some(PieceVariant::Queen),
+        Some(b'r') => Some(PieceVariant::Castle),
+        Some(b'b') => Some(PieceVariant::Bishop),
+        Some(b'n') => Some(PieceVariant::Knight),
+        Some(_) => return Err(malformed()),
+        None => None,
+    };
+
+    data.available_moves
+        .iter()
+        .find(|move_data| {
+            let move_promotion = match move_data.variant {
+                MoveType::Promotion(variant) => Some(variant),
+                _ => None,
+            };
+            move_data.from == from && move_data.to == to && move_promotion == promotion
+        })
+        .copied()
+        .ok_or_else(|| UciError::Illegal(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+    use crate::game::TeamColor;
+
+    #[test]
+    fn round_trips() {
+        for text in [
+            fen::STARTING_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let (_, data) = fen::parse(text).unwrap();
+            for move_data in data.available_moves.iter() {
+                let uci = render(move_data);
+                assert_eq!(parse(&data, &uci), Ok(*move_data), "{}", uci);
+            }
+        }
+    }
+
+    #[test]
+    fn castling_is_a_king_move() {
+        let (_, white) = fen::parse("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let (_, black) = fen::parse("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        for (data, uci, column) in [
+            (&white, "e1g1", 7),
+            (&white, "e1c1", 0),
+            (&black, "e8g8", 7),
+            (&black, "e8c8", 0),
+        ] {
+            let move_data = parse(data, uci).unwrap();
+            assert_eq!(move_data.variant, MoveType::Castling(column));
+            assert_eq!(render(&move_data), uci);
+        }
+    }
+
+    #[test]
+    fn promotion_suffixes() {
+        let (_, data) = fen::parse("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+        for (uci, variant) in [
+            ("e7e8q", PieceVariant::Queen),
+            ("e7e8r", PieceVariant::Castle),
+            ("e7e8b", PieceVariant::Bishop),
+            ("e7e8n", PieceVariant::Knight),
+        ] {
+            let move_data = parse(&data, uci).unwrap();
+            assert_eq!(move_data.variant, MoveType::Promotion(variant));
+            assert_eq!(render(&move_data), uci);
+        }
+        assert_eq!(
+            parse(&data, "e7e8"),
+            Err(UciError::Illegal("e7e8".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_moves() {
+        let (_, data) = fen::parse(fen::STARTING_POSITION).unwrap();
+        for text in [
+            "", "e2", "e2e", "e2e4qq", "e2e9", "i2e4", "e2 e4", "e2e4 ", "e7e8k", "E2E4", "é2e4",
+        ] {
+            assert_eq!(
+                parse(&data, text),
+                Err(UciError::Malformed(text.to_string()))
+            );
+        }
+        for text in ["e2e5", "e1e2", "e7e5", "e2e4q"] {
+            assert_eq!(parse(&data, text), Err(UciError::Illegal(text.to_string())));
+        }
+    }
+
+    #[test]
+    fn ignores_the_side_shown_at_the_bottom() {
+        let (_, mut data) = fen::parse("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let white_view: Vec<Move> = ["a1a8", "e1g1", "h1h2"]
+            .iter()
+            .map(|uci| parse(&data, uci).unwrap())
+            .collect();
+        data.player_color = TeamColor::Black;
+        for (uci, move_data) in ["a1a8", "e1g1", "h1h2"].iter().zip(white_view) {
+            assert_eq!(parse(&data, uci), Ok(move_data));
+            assert_eq!(render(&move_data), *uci);
+        }
+    }
+}
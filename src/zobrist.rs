@@ -0,0 +1,206 @@
+use crate::game::{Board, GameData, Piece, PieceVariant, TeamColor};
+use crate::produce;
+
+/// Steps a splitmix64 generator, usable at compile time.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (next_state, key) = splitmix64(state);
+        keys[i] = key;
+        state = next_state;
+        i += 1;
+    }
+    keys
+}
+
+/// One key per piece kind and color on each of the 64 squares.
+const PIECE_KEYS: [u64; 12 * 64] = generate_keys(0x2545_F491_4F6C_DD1D);
+/// One key per castling right, ordered like `GameData::is_valid_castling`.
+const CASTLING_KEYS: [u64; 4] = generate_keys(0x9FB2_1C65_1E98_DF25);
+/// One key per file of the en passant target.
+const EN_PASSANT_KEYS: [u64; 8] = generate_keys(0x4F1B_BCDC_BFA5_3E0A);
+pub const BLACK_TO_MOVE_KEY: u64 = generate_keys::<1>(0xD1B5_4A32_D192_ED03)[0];
+
+pub fn piece_key(piece: Piece, square: (usize, usize)) -> u64 {
+    let kind = piece.color.index() * 6 + piece.variant.index();
+    PIECE_KEYS[kind * 64 + square.1 * 8 + square.0]
+}
+
+pub fn en_passant_file_key(column: usize) -> u64 {
+    EN_PASSANT_KEYS[column]
+}
+
+pub fn castling_key(is_valid_castling: [[bool; 2]; 2]) -> u64 {
+    let mut key = 0;
+    for (i, is_valid) in is_valid_castling.iter().flatten().enumerate() {
+        if *is_valid {
+            key ^= CASTLING_KEYS[i];
+        }
+    }
+    key
+}
+
+/// The en passant file only counts when the side to move can actually take
+/// the pawn that just advanced: a pawn stands right beside it and taking it
+/// does not leave its own king in check. Positions that differ in nothing
+/// else are the same for the repetition rules.
+pub fn en_passant_key(board: &Board, data: &GameData) -> u64 {
+    let (column, row) = match data.recent_advancing_pawn {
+        Some(square) => square,
+        None => return 0,
+    };
+    let advancing_color = match board[row][column] {
+        Some(piece) => piece.color,
+        None => return 0,
+    };
+    let color = advancing_color.opposite();
+    let target_row = match color {
+        TeamColor::White => row + 1,
+        TeamColor::Black => row.wrapping_sub(1),
+    };
+
+    let can_capture = [column.wrapping_sub(1), column + 1]
+        .into_iter()
+        .filter(|&neighbour| neighbour < 8 && target_row < 8)
+        .filter(|&neighbour| {
+            board[row][neighbour]
+                .is_some_and(|piece| piece.variant == PieceVariant::Pawn && piece.color == color)
+        })
+        .any(|neighbour| {
+            let mut after = *board;
+            after[target_row][column] = after[row][neighbour].take();
+            after[row][column] = None;
+            !produce::is_in_check(&after, data, color)
+        });
+
+    if can_capture {
+        en_passant_file_key(column)
+    } else {
+        0
+    }
+}
+
+/// Computes the key of a position from scratch.
+pub fn hash(board: &Board, data: &GameData) -> u64 {
+    let mut key = 0;
+    for (row, squares) in board.iter().enumerate() {
+        for (column, square) in squares.iter().enumerate() {
+            if let Some(piece) = square {
+                key ^= piece_key(*piece, (column, row));
+            }
+        }
+    }
+
+    key ^= castling_key(data.is_valid_castling);
+    key ^= en_passant_key(board, data);
+    if data.current_turn == TeamColor::Black {
+        key ^= BLACK_TO_MOVE_KEY;
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+    use crate::produce;
+
+    /// Walks the move tree checking the incrementally kept key against a
+    /// recomputation after every make and unmake.
+    fn assert_incremental(board: &mut Board, data: &mut GameData, depth: u32) {
+        assert_eq!(
+            data.hash,
+            hash(board, data),
+            "{}",
+            fen::serialize(board, data)
+        );
+        if depth == 0 {
+            return;
+        }
+
+        for move_data in produce::generate_legal_moves(board, data) {
+            let before = data.hash;
+            let undo = produce::make_move(board, data, &move_data);
+            assert_incremental(board, data, depth - 1);
+            produce::unmake_move(board, data, &undo);
+            assert_eq!(data.hash, before);
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_recomputation() {
+        for position in [
+            fen::STARTING_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            let (mut board, mut data) = fen::parse(position).unwrap();
+            assert_incremental(&mut board, &mut data, 3);
+        }
+    }
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let play = |moves: &[&str]| {
+            let (mut board, mut data) = fen::parse(fen::STARTING_POSITION).unwrap();
+            for uci in moves {
+                data.available_moves = produce::generate_legal_moves(&board, &data);
+                let move_data = crate::uci::parse(&data, uci).unwrap();
+                produce::make_move(&mut board, &mut data, &move_data);
+            }
+            data.hash
+        };
+
+        assert_eq!(
+            play(&["g1f3", "g8f6", "b1c3", "b8c6"]),
+            play(&["b1c3", "b8c6", "g1f3", "g8f6"])
+        );
+        assert_eq!(play(&["g1f3", "g8f6", "f3g1", "f6g8"]), play(&[]));
+        // same pieces and side to move, but the castling rights are gone
+        assert_ne!(
+            play(&["e2e4", "e7e5"]),
+            play(&["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8"])
+        );
+    }
+
+    #[test]
+    fn en_passant_counts_only_when_legal() {
+        let key = |text: &str| {
+            let (board, data) = fen::parse(text).unwrap();
+            let position = crate::bitboard::Position::from_board(&board, &data);
+            assert_eq!(position.hash, data.hash, "{}", text);
+            data.hash
+        };
+
+        // taking on c6 would open the fifth rank to the rook
+        assert_eq!(
+            key("8/8/8/KPp4r/8/8/8/7k w - c6 0 2"),
+            key("8/8/8/KPp4r/8/8/8/7k w - - 0 2")
+        );
+        assert_ne!(
+            key("8/8/8/KPp5/8/8/8/7k w - c6 0 2"),
+            key("8/8/8/KPp5/8/8/8/7k w - - 0 2")
+        );
+        // the pinned pawn is not the only one that can take
+        assert_ne!(
+            key("8/8/8/KPpP3r/8/8/8/7k w - c6 0 2"),
+            key("8/8/8/KPpP3r/8/8/8/7k w - - 0 2")
+        );
+        // a pawn pinned along a diagonal
+        assert_eq!(
+            key("K7/7k/8/8/4pP2/8/8/1B6 b - f3 0 2"),
+            key("K7/7k/8/8/4pP2/8/8/1B6 b - - 0 2")
+        );
+    }
+}